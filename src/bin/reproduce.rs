@@ -0,0 +1,286 @@
+//! Turns a crash found by a campaign into a small, human-readable reproducer.
+//!
+//! Usage: `reproduce [-o <output>] [-t <timeout ms>] [-e <exit code>] <crash file> <target> [args...]`
+//!
+//! The crash file is read as a `PacketBasedInput<TokenStream>` in any of the supported
+//! formats. The target gets the input as a libdesock `.txt` file, either through an
+//! argument `@@` that is replaced by the path of the file or on stdin.
+//! An execution counts as a crash if the target is killed by a signal or exits with
+//! one of the exit codes given with `-e`, like the `exitcode` of a sanitizer.
+//!
+//! The minimized input is written to `<output>.txt` and an annotated listing
+//! that shows every token of every packet to `<output>.listing`.
+//! The output defaults to the crash file with the extension `.min`.
+
+use butterfly::packets::{PacketBasedInput, minimize_packets};
+use butterfly::tokens::{TokenStream, TextToken};
+use libafl::prelude::{Input, Error};
+use std::io::Write;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+/// Whether a target that ended with `status` crashed.
+fn is_crash(status: ExitStatus, crash_codes: &[i32]) -> bool {
+    status.signal().is_some() || status.code().is_some_and(|code| crash_codes.contains(&code))
+}
+
+struct Target {
+    cmdline: Vec<String>,
+    timeout: Duration,
+    crash_codes: Vec<i32>,
+    input_file: PathBuf,
+}
+
+impl Target {
+    /// Runs the target on the txt representation of `input` and returns whether it crashed.
+    fn crashes(&self, input: &PacketBasedInput<TokenStream>) -> Result<bool, Error> {
+        let txt = input.txt_to_vec(usize::MAX).unwrap();
+        std::fs::write(&self.input_file, &txt)?;
+        
+        let input_file = self.input_file.to_string_lossy();
+        let uses_file = self.cmdline.iter().any(|arg| arg == "@@");
+        let args = self.cmdline[1..].iter().map(|arg| if arg == "@@" { input_file.to_string() } else { arg.clone() });
+        
+        let mut child = Command::new(&self.cmdline[0])
+            .args(args)
+            .stdin(if uses_file { Stdio::null() } else { Stdio::piped() })
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        
+        /* Writing from another thread lets the timeout cover a target that does not read its stdin.
+           The thread ends with a broken pipe once the target is gone. */
+        if let Some(mut stdin) = child.stdin.take() {
+            std::thread::spawn(move || {
+                let _ = stdin.write_all(&txt);
+            });
+        }
+        
+        let start = Instant::now();
+        
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok(is_crash(status, &self.crash_codes));
+            }
+            
+            if start.elapsed() > self.timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Ok(false);
+            }
+            
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+fn token_class(token: &TextToken) -> &'static str {
+    match token {
+        TextToken::Constant(_) => "Constant",
+        TextToken::Number(_) => "Number",
+        TextToken::Whitespace(_) => "Whitespace",
+        TextToken::Text(_) => "Text",
+        TextToken::Hex(_) => "Hex",
+        TextToken::Octal(_) => "Octal",
+        TextToken::Float(_) => "Float",
+        TextToken::Quoted(_) => "Quoted",
+        TextToken::Delimiter(_) => "Delimiter",
+        TextToken::Raw(_) => "Raw",
+    }
+}
+
+fn escape(data: &[u8]) -> String {
+    data.iter().flat_map(|c| std::ascii::escape_default(*c)).map(char::from).collect()
+}
+
+fn listing(input: &PacketBasedInput<TokenStream>) -> String {
+    let mut listing = String::new();
+    
+    for (idx, packet) in input.packets().iter().enumerate() {
+        listing.push_str(&format!("Packet #{idx}:\n"));
+        
+        for token in packet.tokens() {
+            listing.push_str(&format!("    {:<12}\"{}\"\n", token_class(token), escape(token.data())));
+        }
+        
+        listing.push('\n');
+    }
+    
+    listing
+}
+
+fn usage() -> ! {
+    eprintln!("Usage: reproduce [-o <output>] [-t <timeout ms>] [-e <exit code>] <crash file> <target> [args...]");
+    std::process::exit(1);
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    output: PathBuf,
+    timeout: Duration,
+    crash_codes: Vec<i32>,
+    crash_file: PathBuf,
+    cmdline: Vec<String>,
+}
+
+/// Parses the command line without the program name, `None` if it is malformed.
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Option<Options> {
+    let mut output = None;
+    let mut timeout = Duration::from_secs(5);
+    let mut crash_codes = vec![];
+    let mut crash_file = None;
+    
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => output = Some(PathBuf::from(args.next()?)),
+            "-t" => timeout = Duration::from_millis(args.next()?.parse::<u64>().ok()?),
+            "-e" => crash_codes.push(args.next()?.parse::<i32>().ok()?),
+            _ => {
+                crash_file = Some(PathBuf::from(arg));
+                break;
+            },
+        }
+    }
+    
+    let crash_file = crash_file?;
+    let cmdline: Vec<String> = args.collect();
+    
+    if cmdline.is_empty() {
+        return None;
+    }
+    
+    Some(Options {
+        output: output.unwrap_or_else(|| crash_file.with_extension("min")),
+        timeout,
+        crash_codes,
+        crash_file,
+        cmdline,
+    })
+}
+
+fn main() -> Result<(), Error> {
+    let Options { output, timeout, crash_codes, crash_file, cmdline } = parse_args(std::env::args().skip(1)).unwrap_or_else(|| usage());
+    let mut input = PacketBasedInput::<TokenStream>::from_file(&crash_file)?;
+    let target = Target {
+        cmdline,
+        timeout,
+        crash_codes,
+        input_file: std::env::temp_dir().join(format!("butterfly-reproduce-{}.txt", std::process::id())),
+    };
+    
+    if !target.crashes(&input)? {
+        let _ = std::fs::remove_file(&target.input_file);
+        eprintln!("{} does not crash the target", crash_file.display());
+        std::process::exit(1);
+    }
+    
+    let packets = input.packets().len();
+    let len = input.txt_len();
+    let mut executions = 0;
+    
+    minimize_packets(&mut input, |candidate| {
+        executions += 1;
+        target.crashes(candidate)
+    })?;
+    
+    let _ = std::fs::remove_file(&target.input_file);
+    
+    let txt_path = append_extension(&output, "txt");
+    let listing_path = append_extension(&output, "listing");
+    std::fs::write(&txt_path, input.txt_to_vec(usize::MAX).unwrap())?;
+    std::fs::write(&listing_path, listing(&input))?;
+    
+    println!("Minimized {} packets ({} bytes) to {} packets ({} bytes) in {} executions", packets, len, input.packets().len(), input.txt_len(), executions);
+    println!("Reproducer: {}", txt_path.display());
+    println!("Listing:    {}", listing_path.display());
+    Ok(())
+}
+
+fn append_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn args(line: &str) -> impl Iterator<Item = String> {
+        line.split(' ').map(String::from).collect::<Vec<_>>().into_iter()
+    }
+    
+    #[test]
+    fn test_parse_args() {
+        let options = parse_args(args("-o out -t 100 -e 3 -e 77 crash.bin ./server @@ -v")).unwrap();
+        assert_eq!(options, Options {
+            output: PathBuf::from("out"),
+            timeout: Duration::from_millis(100),
+            crash_codes: vec![3, 77],
+            crash_file: PathBuf::from("crash.bin"),
+            cmdline: vec!["./server".to_string(), "@@".to_string(), "-v".to_string()],
+        });
+        
+        let options = parse_args(args("crash.bin ./server")).unwrap();
+        assert_eq!(options.output, PathBuf::from("crash.min"));
+        assert_eq!(options.timeout, Duration::from_secs(5));
+        assert!(options.crash_codes.is_empty());
+        
+        assert!(parse_args(args("crash.bin")).is_none());
+        assert!(parse_args(args("-t soon crash.bin ./server")).is_none());
+        assert!(parse_args(args("-e")).is_none());
+    }
+    
+    #[test]
+    fn test_listing() {
+        let input = PacketBasedInput::new(vec![
+            TokenStream::from_bytes(b"USER 42\r\n"),
+            TokenStream::from_bytes(b"QUIT"),
+        ]);
+        assert_eq!(listing(&input), concat!(
+            "Packet #0:\n",
+            "    Text        \"USER\"\n",
+            "    Whitespace  \" \"\n",
+            "    Number      \"42\"\n",
+            "    Whitespace  \"\\r\\n\"\n",
+            "\n",
+            "Packet #1:\n",
+            "    Text        \"QUIT\"\n",
+            "\n",
+        ));
+    }
+    
+    #[test]
+    fn test_is_crash() {
+        let exited = ExitStatus::from_raw(3 << 8);
+        assert!(is_crash(exited, &[3]));
+        assert!(!is_crash(exited, &[1, 2]));
+        
+        let killed = ExitStatus::from_raw(11);
+        assert!(is_crash(killed, &[]));
+        assert!(!is_crash(ExitStatus::from_raw(0), &[3]));
+    }
+    
+    #[test]
+    fn test_toy_target() {
+        let input = PacketBasedInput::new(vec![TokenStream::from_bytes(b"QUIT\r\n")]);
+        let mut target = Target {
+            cmdline: vec!["sh".to_string(), "-c".to_string(), "exit 3".to_string()],
+            timeout: Duration::from_secs(5),
+            crash_codes: vec![3],
+            input_file: std::env::temp_dir().join(format!("butterfly-reproduce-test-{}.txt", std::process::id())),
+        };
+        assert!(target.crashes(&input).unwrap());
+        
+        target.crash_codes = vec![1];
+        assert!(!target.crashes(&input).unwrap());
+        
+        target.cmdline[2] = "kill -SEGV $$".to_string();
+        assert!(target.crashes(&input).unwrap());
+        
+        let _ = std::fs::remove_file(&target.input_file);
+    }
+}
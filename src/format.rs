@@ -0,0 +1,359 @@
+use libafl::prelude::{Error, Corpus, CorpusId, Testcase, Input};
+use libafl_bolts::prelude::write_file_atomic;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::io::Read;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+/// The on-disk formats that inputs can be stored in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum InputFormat {
+    /// The serde representation of the input. Used for files without a known extension.
+    #[default]
+    Postcard,
+    
+    /// Plain text, with packets separated by the libdesock separator.
+    Txt,
+    
+    /// Length-prefixed packets.
+    Frames,
+    
+    /// A pcap or pcapng capture.
+    Pcap,
+}
+
+impl InputFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "txt" => Some(Self::Txt),
+            "frames" => Some(Self::Frames),
+            "pcap" | "pcapng" => Some(Self::Pcap),
+            _ => None,
+        }
+    }
+    
+    /// Picks the format by the extension of `path`, falling back to postcard.
+    pub fn from_path(path: &Path) -> Self {
+        path.extension()
+            .and_then(|s| s.to_str())
+            .and_then(Self::from_extension)
+            .unwrap_or_default()
+    }
+    
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            Self::Postcard => None,
+            Self::Txt => Some("txt"),
+            Self::Frames => Some("frames"),
+            Self::Pcap => Some("pcap"),
+        }
+    }
+}
+
+/// Conversion of an input from and to the supported on-disk formats.
+pub trait FormattedInput: Sized {
+    fn parse_format(format: InputFormat, bytes: &[u8]) -> Option<Self>;
+    
+    fn serialize_format(&self, format: InputFormat) -> Option<Vec<u8>>;
+    
+    /// Whether inputs of this type can be stored in `format` without losing information,
+    /// like the direction, delay or connection of their packets.
+    fn supports_format(format: InputFormat) -> bool;
+    
+    /// Metadata that `format` cannot hold, like the delays of txt files.
+    /// It is written as text into the file at [`sidecar_path`].
+    fn serialize_sidecar(&self, _format: InputFormat) -> Option<Vec<u8>> {
+        None
+    }
+    
+    /// Restores the metadata that [`Self::serialize_sidecar`] wrote.
+    fn parse_sidecar(&mut self, _format: InputFormat, _bytes: &[u8]) -> Option<()> {
+        Some(())
+    }
+}
+
+/// The file next to `path` that holds the metadata that the format of `path` cannot store.
+/// It is hidden, so LibAFL does not load it as an input of its own.
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}.sidecar"))
+}
+
+pub(crate) fn read_file<I: FormattedInput>(path: &Path) -> Result<I, Error> {
+    let mut file = std::fs::File::open(path)?;
+    let mut bytes = vec![];
+    file.read_to_end(&mut bytes)?;
+    
+    let format = InputFormat::from_path(path);
+    let mut input = I::parse_format(format, &bytes).ok_or_else(|| Error::serialize(format!("Could not parse {format:?} file {}", path.display())))?;
+    let sidecar = sidecar_path(path);
+    
+    if sidecar.exists() {
+        let bytes = std::fs::read(&sidecar)?;
+        input.parse_sidecar(format, &bytes).ok_or_else(|| Error::serialize(format!("Could not parse {}", sidecar.display())))?;
+    }
+    
+    Ok(input)
+}
+
+/* Refuses formats that would silently drop parts of the input */
+pub(crate) fn write_file<I: FormattedInput>(input: &I, path: &Path) -> Result<(), Error> {
+    let format = InputFormat::from_path(path);
+    
+    if !I::supports_format(format) {
+        return Err(Error::illegal_argument(format!("{format:?} cannot store inputs of type {}, cannot write {}", std::any::type_name::<I>(), path.display())));
+    }
+    
+    let bytes = input.serialize_format(format).ok_or_else(|| Error::illegal_argument(format!("Cannot write input as {format:?} to {}", path.display())))?;
+    write_file_atomic(path, &bytes)?;
+    
+    /* A stale sidecar of an earlier input would be applied to this one */
+    let sidecar = sidecar_path(path);
+    
+    match input.serialize_sidecar(format) {
+        Some(bytes) => write_file_atomic(&sidecar, &bytes),
+        None if sidecar.exists() => Ok(std::fs::remove_file(&sidecar)?),
+        None => Ok(()),
+    }
+}
+
+/* Corpora remove the files of their testcases but don't know about the sidecars */
+fn remove_orphaned_sidecar<I>(testcase: &Testcase<I>) {
+    if let Some(path) = testcase.file_path() && !path.exists() {
+        let _ = std::fs::remove_file(sidecar_path(path));
+    }
+}
+
+/// A corpus that stores its testcases in one of the [`InputFormat`]s.
+///
+/// LibAFL names corpus files after [`Input::generate_name`] and picks the format by the extension.
+/// This wrapper appends the extension of the format to the names of new testcases,
+/// everything else is left to the inner corpus. Wrap the objective corpus as well to get
+/// crashes in a format that other tools understand.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(bound = "C: Serialize + for<'a> Deserialize<'a>")]
+pub struct FormattedCorpus<C, I> {
+    inner: C,
+    format: InputFormat,
+    phantom: PhantomData<I>,
+}
+
+impl<C, I> FormattedCorpus<C, I>
+where
+    I: FormattedInput,
+{
+    /// Fails if `format` cannot store inputs of type `I`, use [`InputFormat::Postcard`] in that case.
+    pub fn new(inner: C, format: InputFormat) -> Result<Self, Error> {
+        if !I::supports_format(format) {
+            return Err(Error::illegal_argument(format!("{format:?} cannot store inputs of type {}", std::any::type_name::<I>())));
+        }
+        
+        Ok(Self {
+            inner,
+            format,
+            phantom: PhantomData,
+        })
+    }
+    
+    pub fn format(&self) -> InputFormat {
+        self.format
+    }
+    
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+    
+    pub fn inner_mut(&mut self) -> &mut C {
+        &mut self.inner
+    }
+}
+
+impl<C, I> FormattedCorpus<C, I>
+where
+    I: Input,
+{
+    fn set_file_name(&self, testcase: &mut Testcase<I>, id: CorpusId) {
+        let Some(input) = testcase.input() else {
+            return;
+        };
+        let name = input.generate_name(Some(id));
+        
+        *testcase.filename_mut() = Some(match self.format.extension() {
+            Some(extension) => format!("{name}.{extension}"),
+            None => name,
+        });
+    }
+}
+
+impl<C, I> Corpus<I> for FormattedCorpus<C, I>
+where
+    C: Corpus<I>,
+    I: Input,
+{
+    fn count(&self) -> usize {
+        self.inner.count()
+    }
+    
+    fn count_disabled(&self) -> usize {
+        self.inner.count_disabled()
+    }
+    
+    fn count_all(&self) -> usize {
+        self.inner.count_all()
+    }
+    
+    fn add(&mut self, mut testcase: Testcase<I>) -> Result<CorpusId, Error> {
+        if testcase.filename().is_none() {
+            self.set_file_name(&mut testcase, self.inner.peek_free_id());
+        }
+        
+        self.inner.add(testcase)
+    }
+    
+    fn add_disabled(&mut self, mut testcase: Testcase<I>) -> Result<CorpusId, Error> {
+        if testcase.filename().is_none() {
+            self.set_file_name(&mut testcase, self.inner.peek_free_id());
+        }
+        
+        self.inner.add_disabled(testcase)
+    }
+    
+    /// The new testcase gets a new file, even if it was cloned from the old one.
+    fn replace(&mut self, id: CorpusId, mut testcase: Testcase<I>) -> Result<Testcase<I>, Error> {
+        if testcase.input().is_some() {
+            *testcase.file_path_mut() = None;
+            self.set_file_name(&mut testcase, id);
+        }
+        
+        let old = self.inner.replace(id, testcase)?;
+        remove_orphaned_sidecar(&old);
+        Ok(old)
+    }
+    
+    fn remove(&mut self, id: CorpusId) -> Result<Testcase<I>, Error> {
+        let testcase = self.inner.remove(id)?;
+        remove_orphaned_sidecar(&testcase);
+        Ok(testcase)
+    }
+    
+    fn get(&self, id: CorpusId) -> Result<&RefCell<Testcase<I>>, Error> {
+        self.inner.get(id)
+    }
+    
+    fn get_from_all(&self, id: CorpusId) -> Result<&RefCell<Testcase<I>>, Error> {
+        self.inner.get_from_all(id)
+    }
+    
+    fn current(&self) -> &Option<CorpusId> {
+        self.inner.current()
+    }
+    
+    fn current_mut(&mut self) -> &mut Option<CorpusId> {
+        self.inner.current_mut()
+    }
+    
+    fn next(&self, id: CorpusId) -> Option<CorpusId> {
+        self.inner.next(id)
+    }
+    
+    fn peek_free_id(&self) -> CorpusId {
+        self.inner.peek_free_id()
+    }
+    
+    fn prev(&self, id: CorpusId) -> Option<CorpusId> {
+        self.inner.prev(id)
+    }
+    
+    fn first(&self) -> Option<CorpusId> {
+        self.inner.first()
+    }
+    
+    fn last(&self) -> Option<CorpusId> {
+        self.inner.last()
+    }
+    
+    fn nth_from_all(&self, nth: usize) -> CorpusId {
+        self.inner.nth_from_all(nth)
+    }
+    
+    fn load_input_into(&self, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        self.inner.load_input_into(testcase)
+    }
+    
+    fn store_input_from(&self, testcase: &Testcase<I>) -> Result<(), Error> {
+        self.inner.store_input_from(testcase)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::{PacketBasedInput, Timed, Directed, Connected};
+    use crate::tokens::TokenStream;
+    use libafl::prelude::{InMemoryCorpus, OnDiskCorpus};
+    
+    #[test]
+    fn test_roundtrip() {
+        let input = PacketBasedInput::<TokenStream>::parse_txt(b"USER a\r\n--------PASS b\r\n").unwrap();
+        let dir = std::env::temp_dir().join(format!("butterfly-format-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        
+        /* pcap is left out because reading merges the packets into one message */
+        for name in ["input.txt", "input.frames", "input"] {
+            let path = dir.join(name);
+            input.to_file(&path).unwrap();
+            let output = PacketBasedInput::<TokenStream>::from_file(&path).unwrap();
+            assert_eq!(input.txt_to_vec(usize::MAX), output.txt_to_vec(usize::MAX));
+        }
+        
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    fn accepts<I: FormattedInput>(format: InputFormat) -> bool {
+        FormattedCorpus::<InMemoryCorpus<I>, I>::new(InMemoryCorpus::new(), format).is_ok()
+    }
+    
+    #[test]
+    fn test_corpus_format() {
+        assert!(!accepts::<TokenStream>(InputFormat::Frames));
+        assert!(accepts::<PacketBasedInput<Timed<TokenStream>>>(InputFormat::Txt));
+        assert!(accepts::<PacketBasedInput<Timed<TokenStream>>>(InputFormat::Frames));
+        assert!(!accepts::<PacketBasedInput<TokenStream>>(InputFormat::Pcap));
+        
+        let dir = std::env::temp_dir().join(format!("butterfly-corpus-{}", std::process::id()));
+        let mut corpus = FormattedCorpus::new(OnDiskCorpus::new(&dir).unwrap(), InputFormat::Txt).unwrap();
+        let input = PacketBasedInput::<TokenStream>::parse_txt(b"USER a\r\n--------PASS b\r\n").unwrap();
+        let id = corpus.add(Testcase::new(input.clone())).unwrap();
+        
+        let path = corpus.get(id).unwrap().borrow().file_path().clone().unwrap();
+        assert_eq!(path.extension().unwrap(), "txt");
+        assert_eq!(std::fs::read(&path).unwrap(), input.txt_to_vec(usize::MAX).unwrap());
+        
+        /* Inputs outside of a formatted corpus keep plain names */
+        assert_eq!(TokenStream::from_bytes(b"a").generate_name(None).len(), 16);
+        
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn test_write_format() {
+        let dir = std::env::temp_dir().join(format!("butterfly-write-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        
+        let input = PacketBasedInput::new(vec![Directed::client(TokenStream::from_bytes(b"USER a\r\n")), Directed::server(TokenStream::from_bytes(b"331 ok\r\n"))]);
+        assert!(input.to_file(dir.join("directed.txt")).is_err());
+        assert!(input.to_file(dir.join("directed.frames")).is_err());
+        input.to_file(dir.join("directed")).unwrap();
+        
+        let input = PacketBasedInput::new(vec![Connected::new(1, TokenStream::from_bytes(b"USER a\r\n"))]);
+        assert!(input.to_file(dir.join("connected.txt")).is_err());
+        input.to_file(dir.join("connected.frames")).unwrap();
+        
+        let input = PacketBasedInput::new(vec![TokenStream::from_bytes(b"USER a\r\n")]);
+        assert!(input.to_file(dir.join("input.pcap")).is_err());
+        assert!(!dir.join("input.pcap").exists());
+        
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
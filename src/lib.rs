@@ -1,2 +1,8 @@
+pub mod format;
+pub mod net;
 pub mod packets;
+pub mod states;
 pub mod tokens;
+
+#[cfg(test)]
+mod test_utils;
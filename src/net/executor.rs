@@ -0,0 +1,532 @@
+use libafl::prelude::{Executor, ExitKind, HasObservers, HasExecutions, Error};
+use libafl_bolts::prelude::{RefIndexable, Handle, Handled, MatchName, MatchNameRef, MatchFirstType};
+use crate::packets::{PacketMeta, PacketBasedInput, Transport};
+use crate::net::{ResponseObserver, ReplayExitObserver};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream, UdpSocket};
+use std::time::Duration;
+
+/// Once a response has started, it is complete when the target stays silent for this long.
+const RESPONSE_IDLE: Duration = Duration::from_millis(5);
+
+/// The longest wait between two attempts to connect to a target that refused the connection.
+const MAX_CONNECT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// What to do after a packet has been sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// Sleep for a fixed amount of time.
+    Delay(Duration),
+    
+    /// Wait until the target has sent something, but at most for the given duration.
+    Readable(Duration),
+    
+    /// Read the response of the target. No response within the given duration is a timeout.
+    Response(Duration),
+}
+
+/// How a replay ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReplayExit {
+    /// All packets were sent.
+    Ok,
+    
+    /// The target closed a connection before all packets were sent.
+    ConnectionClosed,
+    
+    /// Nothing was listening on the target address.
+    ConnectionRefused,
+    
+    /// The target reset a connection.
+    ConnectionReset,
+    
+    /// The target did not respond in time.
+    Timeout,
+}
+
+impl ReplayExit {
+    /// Only timeouts are visible in the exit kind. A server that closes or resets a connection
+    /// may just reject the input, so detecting crashes is left to whatever monitors the target process.
+    /// The [`crate::net::ReplayExitObserver`] tells the other exits apart.
+    pub fn exit_kind(&self) -> ExitKind {
+        match self {
+            ReplayExit::Timeout => ExitKind::Timeout,
+            ReplayExit::Ok | ReplayExit::ConnectionClosed | ReplayExit::ConnectionRefused | ReplayExit::ConnectionReset => ExitKind::Ok,
+        }
+    }
+    
+    fn from_error(error: &std::io::Error) -> Self {
+        match error.kind() {
+            ErrorKind::ConnectionRefused => ReplayExit::ConnectionRefused,
+            ErrorKind::ConnectionReset => ReplayExit::ConnectionReset,
+            ErrorKind::WouldBlock | ErrorKind::TimedOut => ReplayExit::Timeout,
+            _ => ReplayExit::ConnectionClosed,
+        }
+    }
+}
+
+enum Socket {
+    Tcp(TcpStream),
+    Udp(UdpSocket),
+}
+
+impl Socket {
+    fn send(&mut self, data: &[u8]) -> std::io::Result<()> {
+        match self {
+            Socket::Tcp(stream) => stream.write_all(data),
+            Socket::Udp(socket) => socket.send(data).map(|_| ()),
+        }
+    }
+    
+    fn set_read_timeout(&self, timeout: Duration) -> std::io::Result<()> {
+        /* A zero timeout would mean blocking forever */
+        let timeout = Some(std::cmp::max(timeout, Duration::from_micros(1)));
+        
+        match self {
+            Socket::Tcp(stream) => stream.set_read_timeout(timeout),
+            Socket::Udp(socket) => socket.set_read_timeout(timeout),
+        }
+    }
+    
+    fn peek(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Socket::Tcp(stream) => stream.peek(buf),
+            Socket::Udp(socket) => socket.peek(buf),
+        }
+    }
+    
+    fn recv(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Socket::Tcp(stream) => stream.read(buf),
+            Socket::Udp(socket) => socket.recv(buf),
+        }
+    }
+    
+    fn set_nonblocking(&self, nonblocking: bool) -> std::io::Result<()> {
+        match self {
+            Socket::Tcp(stream) => stream.set_nonblocking(nonblocking),
+            Socket::Udp(socket) => socket.set_nonblocking(nonblocking),
+        }
+    }
+    
+    /// Reads whatever has already arrived without waiting for more.
+    /// Errors are left for the next send or read to report.
+    fn drain(&mut self, response: &mut Vec<u8>) {
+        let mut buf = [0u8; 4096];
+        
+        if self.set_nonblocking(true).is_err() {
+            return;
+        }
+        
+        while let Ok(len @ 1..) = self.recv(&mut buf) {
+            response.extend_from_slice(&buf[..len]);
+        }
+        
+        let _ = self.set_nonblocking(false);
+    }
+}
+
+/// Sends the client packets of a [`PacketBasedInput`] to a server, one connection
+/// per [`Packet::connection`], honoring the [`Packet::delay`] of every packet.
+/// Starting and restarting the server is up to the caller.
+#[derive(Clone, Debug)]
+pub struct NetworkReplayer {
+    addr: SocketAddr,
+    transport: Transport,
+    sync: SyncPolicy,
+    connect_timeout: Duration,
+    connect_retries: usize,
+}
+
+impl NetworkReplayer {
+    pub fn new(addr: SocketAddr, transport: Transport, sync: SyncPolicy) -> Self {
+        Self {
+            addr,
+            transport,
+            sync,
+            connect_timeout: Duration::from_secs(1),
+            connect_retries: 0,
+        }
+    }
+    
+    /// Retry refused connections, for servers that need a moment to come up.
+    /// The wait between two retries starts at one millisecond and doubles up to 100 milliseconds.
+    pub fn set_connect_retries(&mut self, retries: usize) {
+        self.connect_retries = retries;
+    }
+    
+    pub fn set_connect_timeout(&mut self, timeout: Duration) {
+        self.connect_timeout = timeout;
+    }
+    
+    fn connect(&self) -> std::io::Result<Socket> {
+        let mut retries = self.connect_retries;
+        let mut backoff = Duration::from_millis(1);
+        
+        loop {
+            let result = match self.transport {
+                Transport::Tcp => TcpStream::connect_timeout(&self.addr, self.connect_timeout).map(|stream| {
+                    let _ = stream.set_nodelay(true);
+                    Socket::Tcp(stream)
+                }),
+                Transport::Udp => {
+                    let local: SocketAddr = if self.addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" }.parse().unwrap();
+                    UdpSocket::bind(local).and_then(|socket| {
+                        socket.connect(self.addr)?;
+                        Ok(Socket::Udp(socket))
+                    })
+                },
+            };
+            
+            match result {
+                Err(error) if error.kind() == ErrorKind::ConnectionRefused && retries > 0 => {
+                    retries -= 1;
+                    std::thread::sleep(backoff);
+                    backoff = std::cmp::min(backoff * 2, MAX_CONNECT_BACKOFF);
+                },
+                result => return result,
+            }
+        }
+    }
+    
+    /// Waits according to the sync policy and returns whatever the target sent.
+    fn sync(&self, socket: &mut Socket) -> Result<Vec<u8>, ReplayExit> {
+        let mut response = vec![];
+        let mut buf = [0u8; 4096];
+        
+        match self.sync {
+            SyncPolicy::Delay(delay) => {
+                std::thread::sleep(delay);
+                socket.drain(&mut response);
+            },
+            SyncPolicy::Readable(timeout) => {
+                socket.set_read_timeout(timeout).map_err(|e| ReplayExit::from_error(&e))?;
+                
+                match socket.peek(&mut buf) {
+                    Ok(0) if matches!(socket, Socket::Tcp(_)) => return Err(ReplayExit::ConnectionClosed),
+                    Ok(_) => socket.drain(&mut response),
+                    Err(error) => match ReplayExit::from_error(&error) {
+                        ReplayExit::Timeout => {},
+                        exit => return Err(exit),
+                    },
+                }
+            },
+            SyncPolicy::Response(timeout) => {
+                socket.set_read_timeout(timeout).map_err(|e| ReplayExit::from_error(&e))?;
+                
+                loop {
+                    match socket.recv(&mut buf) {
+                        Ok(0) if matches!(socket, Socket::Tcp(_)) => {
+                            if response.is_empty() {
+                                return Err(ReplayExit::ConnectionClosed);
+                            }
+                            break;
+                        },
+                        Ok(len) => {
+                            response.extend_from_slice(&buf[..len]);
+                            
+                            /* A datagram is a complete response */
+                            if matches!(socket, Socket::Udp(_)) {
+                                break;
+                            }
+                            
+                            socket.set_read_timeout(RESPONSE_IDLE).map_err(|e| ReplayExit::from_error(&e))?;
+                        },
+                        Err(error) => match ReplayExit::from_error(&error) {
+                            ReplayExit::Timeout if !response.is_empty() => break,
+                            exit => return Err(exit),
+                        },
+                    }
+                }
+            },
+        }
+        
+        Ok(response)
+    }
+    
+    pub fn replay<P: PacketMeta>(&self, input: &PacketBasedInput<P>) -> ReplayExit {
+        self.replay_with_responses(input, |_, _| {})
+    }
+    
+    /// Like [`NetworkReplayer::replay`] but hands everything the target sent after a packet
+    /// to `on_response`, together with the index of the packet in `input.packets()`.
+    /// With [`SyncPolicy::Delay`] and [`SyncPolicy::Readable`] only the data that has
+    /// already arrived when the wait is over is collected.
+    pub fn replay_with_responses<P, F>(&self, input: &PacketBasedInput<P>, mut on_response: F) -> ReplayExit
+    where
+        P: PacketMeta,
+        F: FnMut(usize, Vec<u8>),
+    {
+        let mut sockets = HashMap::<usize, Socket>::new();
+        
+        for (idx, packet) in input.packets().iter().enumerate() {
+            if !packet.is_client() {
+                continue;
+            }
+            
+            if !packet.delay().is_zero() {
+                std::thread::sleep(packet.delay());
+            }
+            
+            let socket = match sockets.entry(packet.connection()) {
+                std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+                std::collections::hash_map::Entry::Vacant(entry) => match self.connect() {
+                    Ok(socket) => entry.insert(socket),
+                    Err(error) => return ReplayExit::from_error(&error),
+                },
+            };
+            
+            if let Err(error) = socket.send(&packet.content_to_vec()) {
+                return ReplayExit::from_error(&error);
+            }
+            
+            match self.sync(socket) {
+                Ok(response) => on_response(idx, response),
+                Err(exit) => return exit,
+            }
+        }
+        
+        ReplayExit::Ok
+    }
+}
+
+/// An executor that replays [`PacketBasedInput`]s against a server that runs independently
+/// of the fuzzer, for example under a forkserver or a process supervisor.
+///
+/// Closed, reset and refused connections are not crashes, see [`ReplayExit::exit_kind`].
+/// Give a target that is slow to start time to come up with [`NetworkReplayer::set_connect_retries`].
+/// The first [`ResponseObserver`] in the observers collects the responses of the target and
+/// the first [`ReplayExitObserver`] gets the [`ReplayExit`] of every execution.
+pub struct NetworkExecutor<OT> {
+    replayer: NetworkReplayer,
+    observers: OT,
+    responses: Option<Handle<ResponseObserver>>,
+    exits: Option<Handle<ReplayExitObserver>>,
+    last_exit: ReplayExit,
+}
+
+impl<OT> NetworkExecutor<OT>
+where
+    OT: MatchFirstType,
+{
+    pub fn new(replayer: NetworkReplayer, observers: OT) -> Self {
+        let responses = observers.match_first_type::<ResponseObserver>().map(|o| o.handle());
+        let exits = observers.match_first_type::<ReplayExitObserver>().map(|o| o.handle());
+        
+        Self {
+            replayer,
+            observers,
+            responses,
+            exits,
+            last_exit: ReplayExit::Ok,
+        }
+    }
+}
+
+impl<OT> NetworkExecutor<OT> {
+    
+    /// Fill the given [`ResponseObserver`] in `observers` with the responses of the target,
+    /// instead of the first one.
+    pub fn set_response_observer(&mut self, observer: Handle<ResponseObserver>) {
+        self.responses = Some(observer);
+    }
+    
+    /// How the last execution ended, to tell refused connections, resets and timeouts apart.
+    pub fn last_exit(&self) -> ReplayExit {
+        self.last_exit
+    }
+}
+
+impl<OT> HasObservers for NetworkExecutor<OT> {
+    type Observers = OT;
+    
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+    
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+impl<EM, P, S, Z, OT> Executor<EM, PacketBasedInput<P>, S, Z> for NetworkExecutor<OT>
+where
+    P: PacketMeta,
+    S: HasExecutions,
+    OT: MatchName,
+{
+    fn run_target(&mut self, _fuzzer: &mut Z, state: &mut S, _mgr: &mut EM, input: &PacketBasedInput<P>) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+        
+        self.last_exit = match &self.responses {
+            Some(handle) => {
+                let observer = self.observers.get_mut(handle).ok_or_else(|| Error::key_not_found(format!("ResponseObserver {} not found", handle.name())))?;
+                let exit = self.replayer.replay_with_responses(input, |idx, response| observer.record(idx, response));
+                observer.finish(input.packets().len());
+                exit
+            },
+            None => self.replayer.replay(input),
+        };
+        
+        if let Some(handle) = &self.exits {
+            let observer = self.observers.get_mut(handle).ok_or_else(|| Error::key_not_found(format!("ReplayExitObserver {} not found", handle.name())))?;
+            observer.set_exit(self.last_exit);
+        }
+        
+        Ok(self.last_exit.exit_kind())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::Directed;
+    use libafl::prelude::{BytesInput, NopState};
+    use libafl_bolts::prelude::tuple_list;
+    use std::net::TcpListener;
+    
+    fn input() -> PacketBasedInput<BytesInput> {
+        PacketBasedInput::new(vec![
+            BytesInput::from(&b"USER a\r\n"[..]),
+            BytesInput::from(&b"PASS b\r\n"[..]),
+        ])
+    }
+    
+    fn server<F>(handler: F) -> SocketAddr
+    where
+        F: FnOnce(TcpStream) + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            handler(stream);
+        });
+        addr
+    }
+    
+    #[test]
+    fn test_echo() {
+        let addr = server(|mut stream| {
+            let mut buf = [0u8; 1024];
+            while let Ok(len @ 1..) = stream.read(&mut buf) {
+                stream.write_all(&buf[..len]).unwrap();
+            }
+        });
+        let replayer = NetworkReplayer::new(addr, Transport::Tcp, SyncPolicy::Response(Duration::from_secs(1)));
+        assert_eq!(replayer.replay(&input()), ReplayExit::Ok);
+    }
+    
+    #[test]
+    fn test_responses() {
+        let addr = server(|mut stream| {
+            let mut buf = [0u8; 1024];
+            while let Ok(len @ 1..) = stream.read(&mut buf) {
+                stream.write_all(&buf[..len]).unwrap();
+            }
+        });
+        let input = PacketBasedInput::new(vec![
+            Directed::client(BytesInput::from(&b"USER a\r\n"[..])),
+            Directed::server(BytesInput::from(&b"331 password\r\n"[..])),
+            Directed::client(BytesInput::from(&b"PASS bbbbbbbbbbbb\r\n"[..])),
+        ]);
+        let mut observer = ResponseObserver::new("responses", 8);
+        observer.set_truncate(true);
+        
+        let replayer = NetworkReplayer::new(addr, Transport::Tcp, SyncPolicy::Response(Duration::from_secs(1)));
+        let exit = replayer.replay_with_responses(&input, |idx, response| observer.record(idx, response));
+        observer.finish(input.packets().len());
+        
+        assert_eq!(exit, ReplayExit::Ok);
+        assert_eq!(observer.responses(), [b"USER a\r\n".to_vec(), vec![], b"PASS bbb".to_vec()]);
+        assert!(observer.is_truncated(2));
+    }
+    
+    #[test]
+    fn test_executor_responses() {
+        let addr = server(|mut stream| {
+            let mut buf = [0u8; 1024];
+            while let Ok(len @ 1..) = stream.read(&mut buf) {
+                stream.write_all(&buf[..len]).unwrap();
+            }
+        });
+        let replayer = NetworkReplayer::new(addr, Transport::Tcp, SyncPolicy::Response(Duration::from_secs(1)));
+        let mut executor = NetworkExecutor::new(replayer, tuple_list!(ResponseObserver::new("responses", 64)));
+        let mut state = NopState::<PacketBasedInput<BytesInput>>::new();
+        
+        /* The observer is found without further setup */
+        assert_eq!(executor.run_target(&mut (), &mut state, &mut (), &input()).unwrap(), ExitKind::Ok);
+        assert_eq!(executor.observers().0.responses(), [b"USER a\r\n".to_vec(), b"PASS b\r\n".to_vec()]);
+    }
+    
+    #[test]
+    fn test_refused() {
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let replayer = NetworkReplayer::new(addr, Transport::Tcp, SyncPolicy::Delay(Duration::ZERO));
+        assert_eq!(replayer.replay(&input()), ReplayExit::ConnectionRefused);
+        
+        /* A target that is not running neither aborts the fuzzer nor counts as a crash */
+        let mut executor = NetworkExecutor::new(replayer, tuple_list!(ReplayExitObserver::new("exit")));
+        let mut state = NopState::<PacketBasedInput<BytesInput>>::new();
+        assert_eq!(executor.run_target(&mut (), &mut state, &mut (), &input()).unwrap(), ExitKind::Ok);
+        assert_eq!(executor.observers().0.exit(), Some(ReplayExit::ConnectionRefused));
+    }
+    
+    #[test]
+    fn test_reset() {
+        /* Closing a socket with unread data makes the kernel send a RST */
+        let addr = server(|stream| {
+            std::thread::sleep(Duration::from_millis(100));
+            drop(stream);
+        });
+        let replayer = NetworkReplayer::new(addr, Transport::Tcp, SyncPolicy::Response(Duration::from_secs(5)));
+        let mut executor = NetworkExecutor::new(replayer, tuple_list!(ReplayExitObserver::new("exit")));
+        let mut state = NopState::<PacketBasedInput<BytesInput>>::new();
+        
+        /* A server that rejects an input this way has not crashed */
+        assert_eq!(executor.run_target(&mut (), &mut state, &mut (), &input()).unwrap(), ExitKind::Ok);
+        assert_eq!(executor.observers().0.exit(), Some(ReplayExit::ConnectionReset));
+    }
+    
+    #[test]
+    fn test_closed() {
+        let closed = std::io::Error::from(ErrorKind::BrokenPipe);
+        assert_eq!(ReplayExit::from_error(&closed), ReplayExit::ConnectionClosed);
+        let aborted = std::io::Error::from(ErrorKind::ConnectionAborted);
+        assert_eq!(ReplayExit::from_error(&aborted), ReplayExit::ConnectionClosed);
+    }
+    
+    #[test]
+    fn test_timeout() {
+        let addr = server(|stream| {
+            std::thread::sleep(Duration::from_secs(1));
+            drop(stream);
+        });
+        let replayer = NetworkReplayer::new(addr, Transport::Tcp, SyncPolicy::Response(Duration::from_millis(50)));
+        assert_eq!(replayer.replay(&input()), ReplayExit::Timeout);
+    }
+    
+    #[test]
+    fn test_udp() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 1024];
+            
+            while let Ok((len, peer)) = socket.recv_from(&mut buf) {
+                let mut response = b"OK ".to_vec();
+                response.extend_from_slice(&buf[..len]);
+                socket.send_to(&response, peer).unwrap();
+            }
+        });
+        
+        let mut observer = ResponseObserver::new("responses", 64);
+        let replayer = NetworkReplayer::new(addr, Transport::Udp, SyncPolicy::Response(Duration::from_secs(1)));
+        let exit = replayer.replay_with_responses(&input(), |idx, response| observer.record(idx, response));
+        
+        assert_eq!(exit, ReplayExit::Ok);
+        assert_eq!(observer.responses(), [b"OK USER a\r\n".to_vec(), b"OK PASS b\r\n".to_vec()]);
+    }
+}
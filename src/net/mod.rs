@@ -0,0 +1,5 @@
+mod executor;
+mod observer;
+
+pub use executor::*;
+pub use observer::*;
@@ -0,0 +1,140 @@
+use libafl::prelude::{Observer, Error};
+use libafl_bolts::prelude::Named;
+use crate::net::ReplayExit;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// Collects what the target answered to every packet of an input.
+///
+/// The responses are aligned with [`crate::packets::PacketBasedInput::packets()`]:
+/// `responses()[i]` holds everything that was received after packet `i` had been sent.
+/// Server packets and packets that were never sent have an empty response.
+/// The [`crate::net::NetworkExecutor`] fills the first observer of this type among its observers.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ResponseObserver {
+    name: Cow<'static, str>,
+    max_len: usize,
+    truncate: bool,
+    responses: Vec<Vec<u8>>,
+    truncated: Vec<bool>,
+}
+
+impl ResponseObserver {
+    /// Responses longer than `max_len` bytes are dropped unless truncation is enabled.
+    pub fn new(name: &'static str, max_len: usize) -> Self {
+        Self {
+            name: Cow::Borrowed(name),
+            max_len,
+            truncate: false,
+            responses: Vec::new(),
+            truncated: Vec::new(),
+        }
+    }
+    
+    /// Keep the first `max_len` bytes of oversized responses instead of dropping them.
+    pub fn set_truncate(&mut self, truncate: bool) {
+        self.truncate = truncate;
+    }
+    
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+    
+    pub fn responses(&self) -> &[Vec<u8>] {
+        &self.responses
+    }
+    
+    pub fn response(&self, idx: usize) -> Option<&[u8]> {
+        self.responses.get(idx).map(|r| &r[..])
+    }
+    
+    /// Whether the response to packet `idx` exceeded the size limit.
+    pub fn is_truncated(&self, idx: usize) -> bool {
+        self.truncated.get(idx).copied().unwrap_or(false)
+    }
+    
+    pub fn clear(&mut self) {
+        self.responses.clear();
+        self.truncated.clear();
+    }
+    
+    /// Stores the response to the packet at `idx`.
+    pub fn record(&mut self, idx: usize, mut response: Vec<u8>) {
+        if self.responses.len() <= idx {
+            self.responses.resize(idx + 1, Vec::new());
+            self.truncated.resize(idx + 1, false);
+        }
+        
+        if response.len() > self.max_len {
+            self.truncated[idx] = true;
+            
+            if self.truncate {
+                response.truncate(self.max_len);
+            } else {
+                response.clear();
+            }
+        }
+        
+        self.responses[idx] = response;
+    }
+    
+    /// Pads the responses to the number of packets of the executed input.
+    pub(crate) fn finish(&mut self, num_packets: usize) {
+        self.responses.resize(num_packets, Vec::new());
+        self.truncated.resize(num_packets, false);
+    }
+}
+
+impl Named for ResponseObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, S> Observer<I, S> for ResponseObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.clear();
+        Ok(())
+    }
+}
+
+/// Records how the last replay of the [`crate::net::NetworkExecutor`] ended.
+///
+/// Closed, reset and refused connections all end in [`libafl::prelude::ExitKind::Ok`],
+/// feedbacks that need to tell them apart read the [`ReplayExit`] here.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReplayExitObserver {
+    name: Cow<'static, str>,
+    exit: Option<ReplayExit>,
+}
+
+impl ReplayExitObserver {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: Cow::Borrowed(name),
+            exit: None,
+        }
+    }
+    
+    /// How the last execution ended, `None` before the first execution.
+    pub fn exit(&self) -> Option<ReplayExit> {
+        self.exit
+    }
+    
+    pub(crate) fn set_exit(&mut self, exit: ReplayExit) {
+        self.exit = Some(exit);
+    }
+}
+
+impl Named for ReplayExitObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, S> Observer<I, S> for ReplayExitObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.exit = None;
+        Ok(())
+    }
+}
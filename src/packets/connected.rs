@@ -0,0 +1,281 @@
+use libafl::prelude::{MutationResult, Error};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::time::Duration;
+use crate::format::InputFormat;
+use crate::packets::{Packet, PacketMeta, PacketBasedInput, PacketMutator, SplitPacket, SplitBoundary, RandomPacketCreator, MergePacket, ShrinkPacket, ShiftPacket};
+
+const CONNECTION_HEADER_LEN: usize = 8;
+
+/// A packet together with the id of the connection it is sent over.
+///
+/// In frames files the connection is stored in front of the content as a 64-bit little-endian number.
+/// txt files only hold the content, use [`PacketBasedInput::txt_streams`] to get one file per connection.
+#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+pub struct Connected<P> {
+    connection: usize,
+    packet: P,
+}
+
+/// An input for protocols that open more than one connection in a session.
+/// The packets of all connections are kept in the order in which they are sent.
+pub type MultiStreamInput<P> = PacketBasedInput<Connected<P>>;
+
+impl<P> Connected<P> {
+    pub fn new(connection: usize, packet: P) -> Self {
+        Self {
+            connection,
+            packet,
+        }
+    }
+    
+    pub fn set_connection(&mut self, connection: usize) {
+        self.connection = connection;
+    }
+    
+    pub fn packet(&self) -> &P {
+        &self.packet
+    }
+    
+    pub fn packet_mut(&mut self) -> &mut P {
+        &mut self.packet
+    }
+    
+    fn header(&self) -> [u8; CONNECTION_HEADER_LEN] {
+        (self.connection as u64).to_le_bytes()
+    }
+}
+
+impl<P> Packet for Connected<P>
+where
+    P: Packet,
+{
+    fn serialize_content(&self, buffer: &mut [u8]) -> usize {
+        self.packet.serialize_content(buffer)
+    }
+    
+    fn deserialize_content(buffer: &[u8]) -> Option<Self> {
+        Some(Self::new(0, P::deserialize_content(buffer)?))
+    }
+    
+    fn serialized_len(&self) -> usize {
+        self.packet.serialized_len()
+    }
+    
+    fn write_content<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.packet.write_content(writer)
+    }
+}
+
+impl<P> PacketMeta for Connected<P>
+where
+    P: PacketMeta,
+{
+    fn is_client(&self) -> bool {
+        self.packet.is_client()
+    }
+    
+    fn delay(&self) -> Duration {
+        self.packet.delay()
+    }
+    
+    fn set_delay(&mut self, delay: Duration) {
+        self.packet.set_delay(delay);
+    }
+    
+    fn connection(&self) -> usize {
+        self.connection
+    }
+    
+    fn record_len(&self) -> usize {
+        CONNECTION_HEADER_LEN + self.packet.record_len()
+    }
+    
+    fn write_record<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(&self.header())?;
+        self.packet.write_record(writer)
+    }
+    
+    fn parse_record(buffer: &[u8]) -> Option<Self> {
+        let header = buffer.get(..CONNECTION_HEADER_LEN)?;
+        let connection = usize::try_from(u64::from_le_bytes(header.try_into().ok()?)).ok()?;
+        let packet = P::parse_record(&buffer[CONNECTION_HEADER_LEN..])?;
+        Some(Self::new(connection, packet))
+    }
+    
+    fn roundtrips(format: InputFormat) -> bool {
+        format != InputFormat::Txt && P::roundtrips(format)
+    }
+}
+
+impl<P> PacketBasedInput<Connected<P>>
+where
+    P: PacketMeta + Clone,
+{
+    /// The ids of all connections in the order in which they are first used.
+    pub fn connections(&self) -> Vec<usize> {
+        let mut connections = Vec::<usize>::new();
+        
+        for packet in self.packets() {
+            if !connections.contains(&packet.connection) {
+                connections.push(packet.connection);
+            }
+        }
+        
+        connections
+    }
+    
+    /// Splits the input into one stream per connection.
+    pub fn streams(&self) -> BTreeMap<usize, PacketBasedInput<P>> {
+        let mut streams = BTreeMap::<usize, PacketBasedInput<P>>::new();
+        
+        for packet in self.packets() {
+            streams.entry(packet.connection).or_insert_with(|| PacketBasedInput::new(vec![])).packets_mut().push(packet.packet.clone());
+        }
+        
+        streams
+    }
+    
+    /// The libdesock representation of every connection, for harnesses that
+    /// feed each connection from its own file.
+    pub fn txt_streams(&self) -> BTreeMap<usize, Vec<u8>> {
+        self.streams()
+            .into_iter()
+            .map(|(connection, stream)| (connection, stream.txt_to_vec(usize::MAX).unwrap()))
+            .collect()
+    }
+}
+
+impl<P, S, M> PacketMutator<Connected<P>, S> for M
+where
+    M: PacketMutator<P, S>,
+    P: Packet,
+{
+    fn mutate_packet(&mut self, state: &mut S, packet: &mut Connected<P>) -> Result<MutationResult, Error> {
+        self.mutate_packet(state, &mut packet.packet)
+    }
+}
+
+/// The part that is split off stays on the same connection.
+impl<P, S> SplitPacket<S> for Connected<P>
+where
+    P: SplitPacket<S>,
+{
+    fn split_packet(&mut self, state: &mut S) -> Option<Self> {
+        let other = self.packet.split_packet(state)?;
+        Some(Self::new(self.connection, other))
+    }
+    
+    fn split_packet_at(&mut self, state: &mut S, boundary: SplitBoundary) -> Option<Self> {
+        let other = self.packet.split_packet_at(state, boundary)?;
+        Some(Self::new(self.connection, other))
+    }
+}
+
+impl<P, S> RandomPacketCreator<S> for Connected<P>
+where
+    P: RandomPacketCreator<S>,
+{
+    fn create_random_packet(state: &mut S) -> Self {
+        Self::new(0, P::create_random_packet(state))
+    }
+}
+
+/// Only packets on the same connection exchange content.
+impl<P> ShiftPacket for Connected<P>
+where
+    P: ShiftPacket,
+{
+    fn shift_units(&self) -> usize {
+        self.packet.shift_units()
+    }
+    
+    fn shift_boundary(&mut self, next: &mut Self, at: usize) -> bool {
+        self.connection == next.connection && self.packet.shift_boundary(&mut next.packet, at)
+    }
+}
+
+/// Only packets on the same connection are merged.
+impl<P> MergePacket for Connected<P>
+where
+    P: MergePacket,
+{
+    fn merge_packet(&mut self, other: &Self) -> bool {
+        self.connection == other.connection && self.packet.merge_packet(&other.packet)
+    }
+}
+
+impl<P> ShrinkPacket for Connected<P>
+where
+    P: ShrinkPacket + Clone,
+{
+    fn shrink_packet<F>(&mut self, keep: &mut F) -> Result<(), Error>
+    where
+        F: FnMut(&Self) -> Result<bool, Error>,
+    {
+        let connection = self.connection;
+        self.packet.shrink_packet(&mut |packet: &P| keep(&Self::new(connection, packet.clone())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::{PacketInterleaveMutator, PacketConnectionMutator};
+    use crate::test_utils::TestState;
+    use libafl::prelude::{BytesInput, Mutator};
+    
+    fn ftp_session() -> MultiStreamInput<BytesInput> {
+        PacketBasedInput::new(vec![
+            Connected::new(0, BytesInput::from(&b"USER a\r\n"[..])),
+            Connected::new(0, BytesInput::from(&b"PASV\r\n"[..])),
+            Connected::new(1, BytesInput::from(&b"data 1"[..])),
+            Connected::new(0, BytesInput::from(&b"STOR x\r\n"[..])),
+            Connected::new(1, BytesInput::from(&b"data 2"[..])),
+        ])
+    }
+    
+    #[test]
+    fn test_streams() {
+        let input = ftp_session();
+        assert_eq!(input.connections(), [0, 1]);
+        
+        let streams = input.txt_streams();
+        assert_eq!(streams[&0], b"USER a\r\n--------PASV\r\n--------STOR x\r\n");
+        assert_eq!(streams[&1], b"data 1--------data 2");
+        
+        /* The connection ids are not part of the txt content */
+        let txt = input.txt_to_vec(usize::MAX).unwrap();
+        assert_eq!(txt, b"USER a\r\n--------PASV\r\n--------data 1--------STOR x\r\n--------data 2");
+        
+        let frames = input.frames_to_vec(usize::MAX).unwrap();
+        let output = MultiStreamInput::<BytesInput>::parse_frames(&frames).unwrap();
+        assert_eq!(output.txt_streams(), streams);
+    }
+    
+    #[test]
+    fn test_interleave_keeps_order() {
+        let mut state = TestState::new();
+        let mut mutator = PacketInterleaveMutator::new();
+        let mut input = ftp_session();
+        let streams = input.txt_streams();
+        
+        for _ in 0..1000 {
+            mutator.mutate(&mut state, &mut input).unwrap();
+            assert_eq!(input.txt_streams(), streams);
+        }
+    }
+    
+    #[test]
+    fn test_reassign_connection() {
+        let mut state = TestState::new();
+        let mut mutator = PacketConnectionMutator::new(3);
+        let mut input = ftp_session();
+        
+        for _ in 0..100 {
+            mutator.mutate(&mut state, &mut input).unwrap();
+            assert!(input.packets().iter().all(|p| p.connection() < 3));
+        }
+    }
+}
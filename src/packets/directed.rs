@@ -0,0 +1,247 @@
+use libafl::prelude::{MutationResult, Error};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::time::Duration;
+use crate::format::InputFormat;
+use crate::packets::{Packet, PacketMeta, PacketBasedInput, PacketMutator, SplitPacket, SplitBoundary, RandomPacketCreator, MergePacket, ShrinkPacket, ShiftPacket};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum Direction {
+    ClientToServer,
+    ServerToClient,
+}
+
+/// A packet that is tagged with the side of the conversation that sends it.
+/// Packets from the server are the responses that the target is expected to send
+/// and are left alone by all packet mutators.
+#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+pub struct Directed<P> {
+    direction: Direction,
+    packet: P,
+}
+
+impl<P> Directed<P> {
+    pub fn new(direction: Direction, packet: P) -> Self {
+        Self {
+            direction,
+            packet,
+        }
+    }
+    
+    pub fn client(packet: P) -> Self {
+        Self::new(Direction::ClientToServer, packet)
+    }
+    
+    pub fn server(packet: P) -> Self {
+        Self::new(Direction::ServerToClient, packet)
+    }
+    
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+    
+    pub fn packet(&self) -> &P {
+        &self.packet
+    }
+    
+    pub fn packet_mut(&mut self) -> &mut P {
+        &mut self.packet
+    }
+}
+
+impl<P> Packet for Directed<P>
+where
+    P: Packet,
+{
+    fn serialize_content(&self, buffer: &mut [u8]) -> usize {
+        self.packet.serialize_content(buffer)
+    }
+    
+    /// Packets read from txt or frames files are always sent by the client.
+    fn deserialize_content(buffer: &[u8]) -> Option<Self> {
+        Some(Self::client(P::deserialize_content(buffer)?))
+    }
+    
+    fn serialized_len(&self) -> usize {
+        self.packet.serialized_len()
+    }
+    
+    fn write_content<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.packet.write_content(writer)
+    }
+}
+
+impl<P> PacketMeta for Directed<P>
+where
+    P: PacketMeta,
+{
+    fn is_client(&self) -> bool {
+        self.direction == Direction::ClientToServer && self.packet.is_client()
+    }
+    
+    fn delay(&self) -> Duration {
+        self.packet.delay()
+    }
+    
+    fn set_delay(&mut self, delay: Duration) {
+        self.packet.set_delay(delay);
+    }
+    
+    fn connection(&self) -> usize {
+        self.packet.connection()
+    }
+    
+    fn record_len(&self) -> usize {
+        self.packet.record_len()
+    }
+    
+    fn write_record<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.packet.write_record(writer)
+    }
+    
+    fn parse_record(buffer: &[u8]) -> Option<Self> {
+        Some(Self::client(P::parse_record(buffer)?))
+    }
+    
+    /// Server packets are only kept by postcard.
+    fn roundtrips(format: InputFormat) -> bool {
+        format == InputFormat::Postcard
+    }
+}
+
+impl<P> PacketBasedInput<Directed<P>>
+where
+    P: Packet,
+{
+    /// The server packets that directly follow the packet at `idx`.
+    pub fn expected_responses(&self, idx: usize) -> impl Iterator<Item = &P> {
+        self.packets()
+            .iter()
+            .skip(idx + 1)
+            .take_while(|p| p.direction() == Direction::ServerToClient)
+            .map(|p| p.packet())
+    }
+}
+
+impl<P, S, M> PacketMutator<Directed<P>, S> for M
+where
+    M: PacketMutator<P, S>,
+    P: Packet,
+{
+    fn mutate_packet(&mut self, state: &mut S, packet: &mut Directed<P>) -> Result<MutationResult, Error> {
+        self.mutate_packet(state, &mut packet.packet)
+    }
+}
+
+impl<P, S> SplitPacket<S> for Directed<P>
+where
+    P: SplitPacket<S>,
+{
+    fn split_packet(&mut self, state: &mut S) -> Option<Self> {
+        let other = self.packet.split_packet(state)?;
+        Some(Self::new(self.direction, other))
+    }
+    
+    fn split_packet_at(&mut self, state: &mut S, boundary: SplitBoundary) -> Option<Self> {
+        let other = self.packet.split_packet_at(state, boundary)?;
+        Some(Self::new(self.direction, other))
+    }
+}
+
+impl<P, S> RandomPacketCreator<S> for Directed<P>
+where
+    P: RandomPacketCreator<S>,
+{
+    fn create_random_packet(state: &mut S) -> Self {
+        Self::client(P::create_random_packet(state))
+    }
+}
+
+/// Only packets that travel in the same direction exchange content.
+impl<P> ShiftPacket for Directed<P>
+where
+    P: ShiftPacket,
+{
+    fn shift_units(&self) -> usize {
+        self.packet.shift_units()
+    }
+    
+    fn shift_boundary(&mut self, next: &mut Self, at: usize) -> bool {
+        self.direction == next.direction && self.packet.shift_boundary(&mut next.packet, at)
+    }
+}
+
+/// Only packets that travel in the same direction are merged.
+impl<P> MergePacket for Directed<P>
+where
+    P: MergePacket,
+{
+    fn merge_packet(&mut self, other: &Self) -> bool {
+        self.direction == other.direction && self.packet.merge_packet(&other.packet)
+    }
+}
+
+impl<P> ShrinkPacket for Directed<P>
+where
+    P: ShrinkPacket + Clone,
+{
+    fn shrink_packet<F>(&mut self, keep: &mut F) -> Result<(), Error>
+    where
+        F: FnMut(&Self) -> Result<bool, Error>,
+    {
+        let direction = self.direction;
+        self.packet.shrink_packet(&mut |packet: &P| keep(&Self::new(direction, packet.clone())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::{PacketDeleteMutator, PacketSwapMutator, PacketCopyMutator, PacketSplitMutator, PacketRepeatMutator};
+    use crate::test_utils::TestState;
+    use libafl::prelude::{BytesInput, Mutator};
+    
+    fn server_packets(input: &PacketBasedInput<Directed<BytesInput>>) -> Vec<Vec<u8>> {
+        input.packets()
+            .iter()
+            .filter(|p| !p.is_client())
+            .map(|p| p.content_to_vec())
+            .collect()
+    }
+    
+    #[test]
+    fn test_server_packets_untouched() {
+        let mut state = TestState::new();
+        let input = PacketBasedInput::new(vec![
+            Directed::server(BytesInput::from(&b"220 hello\r\n"[..])),
+            Directed::client(BytesInput::from(&b"USER a\r\n"[..])),
+            Directed::server(BytesInput::from(&b"331 password\r\n"[..])),
+            Directed::client(BytesInput::from(&b"PASS b\r\n"[..])),
+            Directed::server(BytesInput::from(&b"230 ok\r\n"[..])),
+        ]);
+        let expected = server_packets(&input);
+        
+        let mut delete = PacketDeleteMutator::new(0);
+        let mut swap = PacketSwapMutator::new();
+        let mut copy = PacketCopyMutator::new(16);
+        let mut split = PacketSplitMutator::new(16);
+        let mut repeat = PacketRepeatMutator::new(16);
+        
+        for _ in 0..100 {
+            let mut input = input.clone();
+            
+            for _ in 0..8 {
+                swap.mutate(&mut state, &mut input).unwrap();
+                copy.mutate(&mut state, &mut input).unwrap();
+                split.mutate(&mut state, &mut input).unwrap();
+                repeat.mutate(&mut state, &mut input).unwrap();
+                delete.mutate(&mut state, &mut input).unwrap();
+            }
+            
+            assert_eq!(server_packets(&input), expected);
+        }
+        
+        assert_eq!(input.expected_responses(1).map(|p| p.content_to_vec()).collect::<Vec<_>>(), [b"331 password\r\n".to_vec()]);
+        assert_eq!(input.txt_to_vec(usize::MAX).unwrap(), b"USER a\r\n--------PASS b\r\n");
+    }
+}
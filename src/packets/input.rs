@@ -1,15 +1,81 @@
-use libafl::prelude::{Input, BytesInput, Error};
-use libafl_bolts::prelude::HasLen;
+use libafl::prelude::{Input, BytesInput, Error, CorpusId};
+use crate::packets::{PcapReader, PcapWriter, Transport};
+use crate::format::{FormattedInput, InputFormat, read_file, write_file};
+use libafl_bolts::prelude::{HasLen, Rand, generic_hash_std};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
-use std::io::Read;
+use std::io::Write;
 use std::hash::Hash;
+use std::time::Duration;
 
 const LIBDESOCK_SEPARATOR: &[u8; 8] = b"--------";
+const FRAME_HEADER_LEN: usize = 4;
 
 pub trait Packet: Sized + Hash {
+    /// Writes as much of the content as fits into `buffer` and returns the number of bytes written.
     fn serialize_content(&self, buffer: &mut [u8]) -> usize;
     fn deserialize_content(buffer: &[u8]) -> Option<Self>;
+    
+    /// The number of bytes the content occupies when serialized.
+    fn serialized_len(&self) -> usize;
+    
+    /// Writes the whole content into `writer`.
+    fn write_content<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let mut buffer = vec![0; self.serialized_len()];
+        let size = self.serialize_content(&mut buffer);
+        writer.write_all(&buffer[..size])
+    }
+    
+    fn content_to_vec(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(self.serialized_len());
+        self.write_content(&mut buffer).expect("writing into a Vec cannot fail");
+        buffer
+    }
+}
+
+/// The metadata that wrappers like [`Directed`](crate::packets::Directed), [`Timed`](crate::packets::Timed)
+/// and [`Connected`](crate::packets::Connected) carry next to the content of a packet.
+/// Plain packets use the defaults, wrappers override what they carry and forward everything else to the inner packet.
+pub trait PacketMeta: Packet {
+    /// Whether the client sends this packet to the target. All other packets are only
+    /// kept for reference: they are neither mutated nor written into the txt and frames formats.
+    fn is_client(&self) -> bool {
+        true
+    }
+    
+    /// How long to wait before sending this packet.
+    fn delay(&self) -> Duration {
+        Duration::ZERO
+    }
+    
+    /// Changes the delay, packets that don't carry one ignore it.
+    fn set_delay(&mut self, _delay: Duration) {}
+    
+    /// The connection that this packet is sent over.
+    fn connection(&self) -> usize {
+        0
+    }
+    
+    /// The number of bytes of the packet in frames files.
+    fn record_len(&self) -> usize {
+        self.serialized_len()
+    }
+    
+    /// Writes the packet into a frames file. Packets that carry metadata
+    /// next to their content store it here. Defaults to the content.
+    /// txt files only ever hold the content because libdesock sends them to the target as they are.
+    fn write_record<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.write_content(writer)
+    }
+    
+    fn parse_record(buffer: &[u8]) -> Option<Self> {
+        Self::deserialize_content(buffer)
+    }
+    
+    /// Whether the txt or frames records of `format` keep everything of this packet.
+    fn roundtrips(_format: InputFormat) -> bool {
+        true
+    }
 }
 
 impl Packet for BytesInput {
@@ -22,8 +88,18 @@ impl Packet for BytesInput {
     fn deserialize_content(buffer: &[u8]) -> Option<Self> {
         Some(Self::from(buffer))
     }
+    
+    fn serialized_len(&self) -> usize {
+        self.as_ref().len()
+    }
+    
+    fn write_content<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(self.as_ref())
+    }
 }
 
+impl PacketMeta for BytesInput {}
+
 #[derive(Clone, Debug, Serialize, Deserialize, Default, Hash)]
 #[serde(bound = "P: Serialize + for<'a> Deserialize<'a>")]
 pub struct PacketBasedInput<P>
@@ -37,6 +113,12 @@ impl<P> PacketBasedInput<P>
 where
     P: Packet,
 {
+    pub fn new(packets: Vec<P>) -> Self {
+        Self {
+            packets,
+        }
+    }
+    
     pub fn packets(&self) -> &[P] {
         &self.packets
     }
@@ -44,15 +126,79 @@ where
     pub fn packets_mut(&mut self) -> &mut Vec<P> {
         &mut self.packets
     }
+}
+
+impl<P> PacketBasedInput<P>
+where
+    P: PacketMeta,
+{
+    /// The packets that are sent to the target.
+    pub fn client_packets(&self) -> impl Iterator<Item = &P> {
+        self.packets.iter().filter(|p| p.is_client())
+    }
+    
+    pub(crate) fn num_client_packets(&self) -> usize {
+        self.client_packets().count()
+    }
+    
+    /// Picks the index of a random client packet.
+    pub(crate) fn random_client_packet<R: Rand>(&self, rand: &mut R) -> Option<usize> {
+        let n = self.num_client_packets();
+        
+        if n == 0 {
+            return None;
+        }
+        
+        let nth = rand.between(0, n - 1);
+        self.packets.iter()
+            .enumerate()
+            .filter(|(_, p)| p.is_client())
+            .nth(nth)
+            .map(|(i, _)| i)
+    }
     
+    /// Writes as much of the libdesock representation as fits into `buf`.
+    /// Use [`Self::write_txt`] or [`Self::txt_to_vec`] to get the complete output.
     pub fn convert_to_txt(&self, buf: &mut [u8]) -> usize {
-        let mut cursor = 0;
-        for packet in &self.packets {
-            cursor += packet.serialize_content(&mut buf[cursor..]);
-            buf[cursor..cursor + LIBDESOCK_SEPARATOR.len()].copy_from_slice(LIBDESOCK_SEPARATOR);
-            cursor += LIBDESOCK_SEPARATOR.len();
+        let len = buf.len();
+        let mut cursor = buf;
+        /* Running out of space only truncates the output */
+        let _ = self.write_txt(&mut cursor);
+        len - cursor.len()
+    }
+    
+    /// The number of bytes of the libdesock representation.
+    pub fn txt_len(&self) -> usize {
+        let content: usize = self.client_packets().map(|p| p.serialized_len()).sum();
+        content + self.num_client_packets().saturating_sub(1) * LIBDESOCK_SEPARATOR.len()
+    }
+    
+    pub fn write_txt<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        for (i, packet) in self.client_packets().enumerate() {
+            if i > 0 {
+                writer.write_all(LIBDESOCK_SEPARATOR)?;
+            }
+            packet.write_content(writer)?;
         }
-        cursor.saturating_sub(LIBDESOCK_SEPARATOR.len())
+        Ok(())
+    }
+    
+    /// Returns the libdesock representation or, if it would be larger than `max_len`, its size.
+    pub fn txt_to_vec(&self, max_len: usize) -> Result<Vec<u8>, usize> {
+        let len = self.txt_len();
+        
+        if len > max_len {
+            return Err(len);
+        }
+        
+        let mut buf = Vec::with_capacity(len);
+        self.write_txt(&mut buf).expect("writing into a Vec cannot fail");
+        Ok(buf)
+    }
+    
+    /// Writes the packets as a synthetic TCP or UDP conversation in pcap format.
+    pub fn convert_to_pcap(&self, transport: Transport, port: u16) -> Vec<u8> {
+        PcapWriter::new(transport, port).write(self)
     }
     
     pub fn parse_txt(buf: &[u8]) -> Option<Self> {
@@ -87,27 +233,160 @@ where
             packets,
         })
     }
+    
+    /// Writes every packet as a frame of a 32-bit little-endian length followed by the content.
+    /// Unlike the libdesock format this round-trips arbitrary packet content.
+    pub fn convert_to_frames(&self, buf: &mut [u8]) -> usize {
+        let len = buf.len();
+        let mut cursor = buf;
+        /* Running out of space only truncates the output */
+        let _ = self.write_frames(&mut cursor);
+        len - cursor.len()
+    }
+    
+    /// The number of bytes of the frames representation.
+    pub fn frames_len(&self) -> usize {
+        self.client_packets().map(|p| FRAME_HEADER_LEN + p.record_len()).sum()
+    }
+    
+    /// Fails if a record does not fit into the 32-bit length of its frame.
+    pub fn write_frames<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        for packet in self.client_packets() {
+            let len = u32::try_from(packet.record_len()).map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "record is too large for a frame"))?;
+            writer.write_all(&len.to_le_bytes())?;
+            packet.write_record(writer)?;
+        }
+        Ok(())
+    }
+    
+    /// Returns the frames representation or, if it would be larger than `max_len`
+    /// or a record is too large for a frame, its size.
+    pub fn frames_to_vec(&self, max_len: usize) -> Result<Vec<u8>, usize> {
+        let len = self.frames_len();
+        
+        if len > max_len || self.client_packets().any(|p| u32::try_from(p.record_len()).is_err()) {
+            return Err(len);
+        }
+        
+        let mut buf = Vec::with_capacity(len);
+        self.write_frames(&mut buf).expect("writing into a Vec cannot fail");
+        Ok(buf)
+    }
+    
+    pub fn parse_frames(buf: &[u8]) -> Option<Self> {
+        let mut cursor = 0;
+        let mut packets = vec![];
+        
+        while cursor < buf.len() {
+            let header = buf.get(cursor..cursor + FRAME_HEADER_LEN)?;
+            let size = u32::from_le_bytes(header.try_into().ok()?) as usize;
+            cursor += FRAME_HEADER_LEN;
+            
+            let data = buf.get(cursor..cursor.checked_add(size)?)?;
+            packets.push(P::parse_record(data)?);
+            cursor += size;
+        }
+        
+        Some(Self {
+            packets,
+        })
+    }
+    
+    /// Reads the client side of the first conversation in a pcap or pcapng capture,
+    /// with one packet per application-level message.
+    pub fn parse_pcap(buf: &[u8]) -> Option<Self> {
+        PcapReader::default().read(buf)
+    }
+}
+
+impl<P> FormattedInput for PacketBasedInput<P>
+where
+    P: PacketMeta + Serialize + for<'a> Deserialize<'a>,
+{
+    fn parse_format(format: InputFormat, bytes: &[u8]) -> Option<Self> {
+        match format {
+            InputFormat::Postcard => postcard::from_bytes(bytes).ok(),
+            InputFormat::Txt => Self::parse_txt(bytes),
+            InputFormat::Frames => Self::parse_frames(bytes),
+            InputFormat::Pcap => Self::parse_pcap(bytes),
+        }
+    }
+    
+    fn serialize_format(&self, format: InputFormat) -> Option<Vec<u8>> {
+        match format {
+            InputFormat::Postcard => postcard::to_allocvec(self).ok(),
+            InputFormat::Txt => self.txt_to_vec(usize::MAX).ok(),
+            InputFormat::Frames => self.frames_to_vec(usize::MAX).ok(),
+            InputFormat::Pcap => Some(PcapWriter::default().write(self)),
+        }
+    }
+    
+    /// Captures are left out because reading them merges the packets into messages.
+    fn supports_format(format: InputFormat) -> bool {
+        match format {
+            InputFormat::Postcard => true,
+            InputFormat::Txt | InputFormat::Frames => P::roundtrips(format),
+            InputFormat::Pcap => false,
+        }
+    }
+    
+    /// The delays of the client packets of txt files, in microseconds and one per line.
+    fn serialize_sidecar(&self, format: InputFormat) -> Option<Vec<u8>> {
+        if format != InputFormat::Txt || self.client_packets().all(|p| p.delay().is_zero()) {
+            return None;
+        }
+        
+        let mut text = String::new();
+        
+        for packet in self.client_packets() {
+            text.push_str(&format!("{}\n", packet.delay().as_micros()));
+        }
+        
+        Some(text.into_bytes())
+    }
+    
+    fn parse_sidecar(&mut self, format: InputFormat, bytes: &[u8]) -> Option<()> {
+        if format != InputFormat::Txt {
+            return Some(());
+        }
+        
+        let delays = std::str::from_utf8(bytes).ok()?
+            .lines()
+            .map(|line| line.trim().parse::<u64>().ok())
+            .collect::<Option<Vec<u64>>>()?;
+        
+        if delays.len() != self.num_client_packets() {
+            return None;
+        }
+        
+        for (packet, micros) in self.packets.iter_mut().filter(|p| p.is_client()).zip(delays) {
+            packet.set_delay(Duration::from_micros(micros));
+        }
+        
+        Some(())
+    }
 }
 
 impl<P> Input for PacketBasedInput<P>
 where
-    P: Packet + std::fmt::Debug + Serialize + for<'a> Deserialize<'a> + Clone,
+    P: PacketMeta + std::fmt::Debug + Serialize + for<'a> Deserialize<'a> + Clone,
 {
     fn from_file<F>(path: F) -> Result<Self, Error>
     where
         F: AsRef<Path>,
     {
-        let path = path.as_ref();
-        let mut file = std::fs::File::open(path)?;
-        let mut bytes = vec![];
-        file.read_to_end(&mut bytes)?;
-        
-        match path.extension().and_then(|s| s.to_str()) {
-            Some("txt") => {
-                Self::parse_txt(&bytes).ok_or_else(|| Error::serialize(format!("Could not parse txt file {}", path.display())))
-            },
-            _ => Ok(postcard::from_bytes(&bytes)?),
-        }
+        read_file(path.as_ref())
+    }
+    
+    fn to_file<F>(&self, path: F) -> Result<(), Error>
+    where
+        F: AsRef<Path>,
+    {
+        write_file(self, path.as_ref())
+    }
+    
+    fn generate_name(&self, _id: Option<CorpusId>) -> String {
+        format!("{:016x}", generic_hash_std(self))
     }
 }
 
@@ -124,6 +403,7 @@ where
 mod tests {
     use super::*;
     use crate::tokens::TokenStream;
+    use libafl_bolts::prelude::{StdRand, current_nanos, Rand};
     
     #[test]
     fn test_txt() {
@@ -134,4 +414,77 @@ mod tests {
         let size = input.convert_to_txt(&mut buf);
         println!("{}", std::str::from_utf8(&buf[..size]).unwrap());
     }
+    
+    #[test]
+    fn test_txt_limits() {
+        let input = PacketBasedInput::<TokenStream>::parse_txt(b"USER a\r\n--------PASS b\r\n").unwrap();
+        assert_eq!(input.txt_len(), 24);
+        assert_eq!(input.txt_to_vec(23), Err(24));
+        assert_eq!(input.txt_to_vec(24).unwrap(), b"USER a\r\n--------PASS b\r\n");
+        
+        /* Too small buffers get truncated output instead of a panic */
+        let mut buf = [0u8; 12];
+        assert_eq!(input.convert_to_txt(&mut buf), 12);
+        assert_eq!(&buf, b"USER a\r\n----");
+    }
+    
+    #[test]
+    fn test_frames_roundtrip() {
+        let mut rand = StdRand::with_seed(current_nanos());
+        let mut buf = vec![0u8; 64 * 1024];
+        
+        for _ in 0..1000 {
+            let mut packets = vec![];
+            
+            for _ in 0..rand.between(0, 8) {
+                let mut data = vec![0u8; rand.between(0, 64)];
+                
+                for byte in &mut data {
+                    /* Bias towards the bytes of the libdesock separator */
+                    *byte = if rand.coinflip(0.5) { b'-' } else { rand.next() as u8 };
+                }
+                
+                packets.push(BytesInput::from(data));
+            }
+            
+            let input = PacketBasedInput::new(packets);
+            let size = input.convert_to_frames(&mut buf);
+            let output = PacketBasedInput::<BytesInput>::parse_frames(&buf[..size]).unwrap();
+            assert_eq!(input.packets(), output.packets());
+        }
+    }
+    
+    /* Claims to be 4 GiB large without allocating anything */
+    #[derive(Hash)]
+    struct HugePacket;
+    
+    impl Packet for HugePacket {
+        fn serialize_content(&self, _buffer: &mut [u8]) -> usize {
+            0
+        }
+        
+        fn deserialize_content(_buffer: &[u8]) -> Option<Self> {
+            None
+        }
+        
+        fn serialized_len(&self) -> usize {
+            u32::MAX as usize + 1
+        }
+        
+        fn write_content<W: Write>(&self, _writer: &mut W) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    
+    impl PacketMeta for HugePacket {}
+    
+    #[test]
+    fn test_frames_limits() {
+        let input = PacketBasedInput::new(vec![HugePacket]);
+        let mut buf = vec![];
+        
+        assert_eq!(input.write_frames(&mut buf).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        assert!(buf.is_empty());
+        assert_eq!(input.frames_to_vec(usize::MAX), Err(FRAME_HEADER_LEN + u32::MAX as usize + 1));
+    }
 }
@@ -0,0 +1,425 @@
+use libafl::prelude::{
+    BytesInput, HasMutatorBytes, Stage, Restartable, RetryCountRestartHelper, ExecutesInput,
+    HasObservers, Corpus, HasCurrentTestcase, HasCurrentCorpusId, HasNamedMetadata,
+    HasMetadata, ExitKind, Error, HasFeedback, HasScheduler, Feedback, RemovableScheduler, HasExecutions, Testcase,
+};
+use libafl_bolts::prelude::{Named, Handle, Handled, MatchName, MatchNameRef, generic_hash_std};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use crate::packets::{PacketMeta, PacketBasedInput, MergePacket};
+
+/// Packets that know how to make their content smaller.
+pub trait ShrinkPacket: Sized {
+    /// Tries smaller versions of this packet and keeps every one that `keep` accepts.
+    /// The default does nothing.
+    fn shrink_packet<F>(&mut self, keep: &mut F) -> Result<(), Error>
+    where
+        F: FnMut(&Self) -> Result<bool, Error>,
+    {
+        let _ = keep;
+        Ok(())
+    }
+}
+
+/// Removes chunks of bytes, starting with large chunks.
+impl ShrinkPacket for BytesInput {
+    fn shrink_packet<F>(&mut self, keep: &mut F) -> Result<(), Error>
+    where
+        F: FnMut(&Self) -> Result<bool, Error>,
+    {
+        let mut chunk = std::cmp::max(self.mutator_bytes().len() / 2, 1);
+        
+        loop {
+            let mut start = 0;
+            
+            while start < self.mutator_bytes().len() {
+                let end = std::cmp::min(start + chunk, self.mutator_bytes().len());
+                let mut candidate = self.mutator_bytes().to_vec();
+                candidate.drain(start..end);
+                let candidate = BytesInput::from(candidate);
+                
+                if keep(&candidate)? {
+                    *self = candidate;
+                } else {
+                    start += chunk;
+                }
+            }
+            
+            if chunk == 1 {
+                break;
+            }
+            
+            chunk /= 2;
+        }
+        
+        Ok(())
+    }
+}
+
+fn client_indices<P: PacketMeta>(input: &PacketBasedInput<P>) -> Vec<usize> {
+    input.packets()
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| p.is_client())
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+/// Deletes client packets, first in large ranges, then one by one, as long as `oracle` accepts the result.
+/// Returns whether anything was deleted.
+pub fn delete_packets<P, F>(input: &mut PacketBasedInput<P>, oracle: &mut F) -> Result<bool, Error>
+where
+    P: PacketMeta + Clone,
+    F: FnMut(&PacketBasedInput<P>) -> Result<bool, Error>,
+{
+    let mut changed = false;
+    let mut chunk = std::cmp::max(input.num_client_packets() / 2, 1);
+    
+    loop {
+        let mut start = 0;
+        let mut clients = client_indices(input);
+        
+        while start < clients.len() {
+            let deleted = &clients[start..std::cmp::min(start + chunk, clients.len())];
+            let candidate = PacketBasedInput::new(
+                input.packets()
+                    .iter()
+                    .enumerate()
+                    .filter(|(idx, _)| !deleted.contains(idx))
+                    .map(|(_, p)| p.clone())
+                    .collect()
+            );
+            
+            if oracle(&candidate)? {
+                *input = candidate;
+                clients = client_indices(input);
+                changed = true;
+            } else {
+                start += chunk;
+            }
+        }
+        
+        if chunk == 1 {
+            break;
+        }
+        
+        chunk /= 2;
+    }
+    
+    Ok(changed)
+}
+
+/// Merges adjacent client packets as long as `oracle` accepts the result.
+/// Returns whether anything was merged.
+pub fn merge_packets<P, F>(input: &mut PacketBasedInput<P>, oracle: &mut F) -> Result<bool, Error>
+where
+    P: PacketMeta + Clone + MergePacket,
+    F: FnMut(&PacketBasedInput<P>) -> Result<bool, Error>,
+{
+    let mut changed = false;
+    let mut idx = 0;
+    
+    while idx + 1 < input.packets().len() {
+        let packets = input.packets();
+        
+        if !packets[idx].is_client() || !packets[idx + 1].is_client() {
+            idx += 1;
+            continue;
+        }
+        
+        let mut merged = packets[idx].clone();
+        
+        if !merged.merge_packet(&packets[idx + 1]) {
+            idx += 1;
+            continue;
+        }
+        
+        let mut candidate = input.clone();
+        candidate.packets_mut()[idx] = merged;
+        candidate.packets_mut().remove(idx + 1);
+        
+        if oracle(&candidate)? {
+            *input = candidate;
+            changed = true;
+        } else {
+            idx += 1;
+        }
+    }
+    
+    Ok(changed)
+}
+
+/// Shrinks the content of every client packet with its [`ShrinkPacket`] implementation.
+pub fn shrink_packets<P, F>(input: &mut PacketBasedInput<P>, oracle: &mut F) -> Result<(), Error>
+where
+    P: PacketMeta + Clone + ShrinkPacket,
+    F: FnMut(&PacketBasedInput<P>) -> Result<bool, Error>,
+{
+    for idx in client_indices(input) {
+        let mut packet = input.packets()[idx].clone();
+        
+        packet.shrink_packet(&mut |candidate: &P| {
+            let mut test = input.clone();
+            test.packets_mut()[idx] = candidate.clone();
+            oracle(&test)
+        })?;
+        
+        input.packets_mut()[idx] = packet;
+    }
+    
+    Ok(())
+}
+
+/// Makes an input as small as possible while `oracle` still accepts it:
+/// deletes packets, merges adjacent packets and shrinks the remaining packets.
+pub fn minimize_packets<P, F>(input: &mut PacketBasedInput<P>, mut oracle: F) -> Result<(), Error>
+where
+    P: PacketMeta + Clone + MergePacket + ShrinkPacket,
+    F: FnMut(&PacketBasedInput<P>) -> Result<bool, Error>,
+{
+    delete_packets(input, &mut oracle)?;
+    merge_packets(input, &mut oracle)?;
+    shrink_packets(input, &mut oracle)
+}
+
+/// Marks testcases that went through the [`PacketMinimizerStage`] or the [`crate::tokens::TokenMinimizerStage`].
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct PacketsMinimized;
+
+libafl_bolts::impl_serdeany!(PacketsMinimized);
+
+/// A stage that minimizes every testcase of the corpus once with [`minimize_packets`].
+///
+/// A smaller input is kept if it produces the same hash of the observer `C`,
+/// usually the coverage map. Inputs that crash or time out only have to keep their exit kind.
+/// The minimized input replaces the testcase in the corpus, with fresh metadata from the feedback,
+/// and the scheduler is told about the replacement.
+pub struct PacketMinimizerStage<C, P> {
+    observer: Handle<C>,
+    phantom: PhantomData<P>,
+}
+
+impl<C, P> PacketMinimizerStage<C, P>
+where
+    C: Handled,
+{
+    pub fn new(observer: &C) -> Self {
+        Self {
+            observer: observer.handle(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<C, P> Named for PacketMinimizerStage<C, P> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("PacketMinimizerStage");
+        &NAME
+    }
+}
+
+impl<C, P, S> Restartable<S> for PacketMinimizerStage<C, P>
+where
+    S: HasNamedMetadata + HasCurrentCorpusId,
+{
+    fn should_restart(&mut self, state: &mut S) -> Result<bool, Error> {
+        /* The stage is not restart-safe */
+        RetryCountRestartHelper::no_retry(state, self.name())
+    }
+    
+    fn clear_progress(&mut self, state: &mut S) -> Result<(), Error> {
+        RetryCountRestartHelper::clear_progress(state, self.name())
+    }
+}
+
+impl<C, E, EM, P, S, Z> Stage<E, EM, S, Z> for PacketMinimizerStage<C, P>
+where
+    C: Hash,
+    E: HasObservers,
+    E::Observers: MatchName,
+    P: PacketMeta + Clone + MergePacket + ShrinkPacket,
+    S: HasCurrentTestcase<PacketBasedInput<P>> + HasCurrentCorpusId + HasExecutions,
+    Z: ExecutesInput<E, EM, PacketBasedInput<P>, S> + HasFeedback + HasScheduler<PacketBasedInput<P>, S>,
+    Z::Feedback: Feedback<EM, PacketBasedInput<P>, E::Observers, S>,
+    Z::Scheduler: RemovableScheduler<PacketBasedInput<P>, S>,
+{
+    fn perform(&mut self, fuzzer: &mut Z, executor: &mut E, state: &mut S, manager: &mut EM) -> Result<(), Error> {
+        minimize_current_testcase(&self.observer, fuzzer, executor, state, manager, |input, oracle| minimize_packets(input, oracle))
+    }
+}
+
+/// Minimizes the current testcase once, keeping the hash of `observer` or the exit kind
+/// if the testcase crashes or times out, and writes the result back into the corpus.
+///
+/// Metadata of the old testcase, like state traces or the scheduled state, refers to packets
+/// that may be gone. So a smaller input gets a new testcase whose metadata the feedback computes
+/// from a final execution, like the tmin stage of LibAFL does.
+pub(crate) fn minimize_current_testcase<C, E, EM, I, M, S, Z>(observer: &Handle<C>, fuzzer: &mut Z, executor: &mut E, state: &mut S, manager: &mut EM, minimize: M) -> Result<(), Error>
+where
+    C: Hash,
+    E: HasObservers,
+    E::Observers: MatchName,
+    I: Clone + Hash,
+    M: FnOnce(&mut I, &mut dyn FnMut(&I) -> Result<bool, Error>) -> Result<(), Error>,
+    S: HasCurrentTestcase<I> + HasCurrentCorpusId + HasExecutions,
+    Z: ExecutesInput<E, EM, I, S> + HasFeedback + HasScheduler<I, S>,
+    Z::Feedback: Feedback<EM, I, E::Observers, S>,
+    Z::Scheduler: RemovableScheduler<I, S>,
+{
+    let Some(id) = state.current_corpus_id()? else {
+        return Ok(());
+    };
+    
+    if state.current_testcase()?.has_metadata::<PacketsMinimized>() {
+        return Ok(());
+    }
+    
+    let mut input = state.current_input_cloned()?;
+    let input_hash = generic_hash_std(&input);
+    let mut run = |state: &mut S, input: &I| -> Result<(ExitKind, u64), Error> {
+        let exit_kind = fuzzer.execute_input(state, executor, manager, input)?;
+        let observers = executor.observers();
+        let observer = observers.get(observer).ok_or_else(|| Error::key_not_found(format!("Observer {} not found", observer.name())))?;
+        Ok((exit_kind, generic_hash_std(observer)))
+    };
+    
+    let (exit_kind, hash) = run(state, &input)?;
+    
+    minimize(&mut input, &mut |candidate| {
+        let (new_exit_kind, new_hash) = run(state, candidate)?;
+        
+        Ok(match exit_kind {
+            ExitKind::Ok => new_exit_kind == ExitKind::Ok && new_hash == hash,
+            _ => new_exit_kind == exit_kind,
+        })
+    })?;
+    
+    if generic_hash_std(&input) == input_hash {
+        state.current_testcase_mut()?.add_metadata(PacketsMinimized);
+        return Ok(());
+    }
+    
+    let exit_kind = fuzzer.execute_input(state, executor, manager, &input)?;
+    let observers = executor.observers();
+    fuzzer.feedback_mut().is_interesting(state, manager, &input, &*observers, &exit_kind)?;
+    
+    let mut testcase = Testcase::from(input);
+    testcase.set_executions(*state.executions());
+    testcase.set_parent_id(id);
+    testcase.add_metadata(PacketsMinimized);
+    fuzzer.feedback_mut().append_metadata(state, manager, &*observers, &mut testcase)?;
+    
+    let prev = state.corpus_mut().replace(id, testcase)?;
+    fuzzer.scheduler_mut().on_replace(state, id, &prev)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::states::{StateScheduler, StateSelection, StateTrace, ScheduledState, ReachedStates};
+    use libafl::prelude::{
+        Executor, Observer, StdFuzzer, StdState, InMemoryCorpus, ConstFeedback, Scheduler, HasCorpus,
+    };
+    use libafl_bolts::prelude::{RefIndexable, StdRand, tuple_list, tuple_list_type};
+    
+    #[derive(Hash)]
+    struct NopObserver(Cow<'static, str>);
+    
+    impl Named for NopObserver {
+        fn name(&self) -> &Cow<'static, str> {
+            &self.0
+        }
+    }
+    
+    impl<I, S> Observer<I, S> for NopObserver {}
+    
+    /// Crashes on every input that contains "CRASH".
+    struct CrashExecutor {
+        observers: tuple_list_type!(NopObserver),
+    }
+    
+    impl HasObservers for CrashExecutor {
+        type Observers = tuple_list_type!(NopObserver);
+        
+        fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+            RefIndexable::from(&self.observers)
+        }
+        
+        fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+            RefIndexable::from(&mut self.observers)
+        }
+    }
+    
+    impl<EM, S, Z> Executor<EM, PacketBasedInput<BytesInput>, S, Z> for CrashExecutor
+    where
+        S: HasExecutions,
+    {
+        fn run_target(&mut self, _fuzzer: &mut Z, state: &mut S, _mgr: &mut EM, input: &PacketBasedInput<BytesInput>) -> Result<ExitKind, Error> {
+            *state.executions_mut() += 1;
+            let content: Vec<u8> = input.packets().iter().flat_map(|p| p.mutator_bytes().to_vec()).collect();
+            Ok(if content.windows(5).any(|w| w == b"CRASH") { ExitKind::Crash } else { ExitKind::Ok })
+        }
+    }
+    
+    #[test]
+    fn test_minimize() {
+        let mut input = PacketBasedInput::new(vec![
+            BytesInput::from(&b"USER a\r\n"[..]),
+            BytesInput::from(&b"PASS b\r\n"[..]),
+            BytesInput::from(&b"SITE CR"[..]),
+            BytesInput::from(&b"ASH\r\n"[..]),
+            BytesInput::from(&b"QUIT\r\n"[..]),
+        ]);
+        
+        minimize_packets(&mut input, |candidate| {
+            let content: Vec<u8> = candidate.packets().iter().flat_map(|p| p.mutator_bytes().to_vec()).collect();
+            Ok(content.windows(5).any(|w| w == b"CRASH"))
+        }).unwrap();
+        
+        assert_eq!(input.packets(), [BytesInput::from(&b"CRASH"[..])]);
+    }
+    
+    #[test]
+    fn test_minimizer_stage() {
+        let mut feedback = ConstFeedback::False;
+        let mut objective = ConstFeedback::False;
+        let mut state = StdState::new(StdRand::with_seed(0), InMemoryCorpus::new(), InMemoryCorpus::new(), &mut feedback, &mut objective).unwrap();
+        let mut scheduler = StateScheduler::new(StateSelection::Rarity);
+        let observer = NopObserver(Cow::Borrowed("nop"));
+        let mut stage = PacketMinimizerStage::<_, BytesInput>::new(&observer);
+        let mut executor = CrashExecutor {
+            observers: tuple_list!(observer),
+        };
+        
+        let mut testcase = Testcase::new(PacketBasedInput::new(vec![
+            BytesInput::from(&b"USER a\r\n"[..]),
+            BytesInput::from(&b"SITE CRASH\r\n"[..]),
+            BytesInput::from(&b"QUIT\r\n"[..]),
+        ]));
+        let mut trace = StateTrace::new();
+        trace.push(0, 331);
+        trace.push(2, 221);
+        testcase.add_metadata(trace);
+        testcase.add_metadata(ScheduledState {
+            state: 221,
+            packet: 2,
+        });
+        
+        let id = state.corpus_mut().add(testcase).unwrap();
+        scheduler.on_add(&mut state, id).unwrap();
+        
+        let mut fuzzer = StdFuzzer::new(scheduler, feedback, objective);
+        state.set_corpus_id(id).unwrap();
+        stage.perform(&mut fuzzer, &mut executor, &mut state, &mut ()).unwrap();
+        
+        /* The old packet indices are gone together with the packets */
+        let testcase = state.corpus().get(id).unwrap().borrow();
+        assert_eq!(testcase.input().as_ref().unwrap().packets(), [BytesInput::from(&b"CRASH"[..])]);
+        assert!(testcase.has_metadata::<PacketsMinimized>());
+        assert!(!testcase.has_metadata::<StateTrace>());
+        assert!(!testcase.has_metadata::<ReachedStates>());
+        assert!(!testcase.has_metadata::<ScheduledState>());
+    }
+}
@@ -1,5 +1,17 @@
 mod input;
 mod mutators;
+mod pcap;
+mod directed;
+mod timed;
+mod connected;
+mod minimize;
+mod stream;
 
 pub use input::*;
 pub use mutators::*;
+pub use pcap::*;
+pub use directed::*;
+pub use timed::*;
+pub use connected::*;
+pub use minimize::*;
+pub use stream::*;
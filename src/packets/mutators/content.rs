@@ -5,7 +5,7 @@ use libafl::prelude::{
     BytesInput, HasMetadata, HasCorpus, HasMaxSize,
     MutationId, MutatorsTuple,
 };
-use crate::packets::{PacketBasedInput, Packet};
+use crate::packets::{PacketBasedInput, Packet, PacketMeta};
 use std::marker::PhantomData;
 use std::borrow::Cow;
 use std::num::NonZero;
@@ -54,17 +54,14 @@ where
 impl<P, S, M> Mutator<PacketBasedInput<P>, S> for PacketContentMutator<P, S, M>
 where
     M: PacketMutator<P, S>,
-    P: Packet,
+    P: PacketMeta,
     S: HasRand,
 {
     fn mutate(&mut self, state: &mut S, input: &mut PacketBasedInput<P>) -> Result<MutationResult, Error> {
-        let len = input.packets().len();
-        
-        if len == 0 {
+        let Some(idx) = input.random_client_packet(state.rand_mut()) else {
             return Ok(MutationResult::Skipped);
-        }
+        };
         
-        let idx = state.rand_mut().between(0, len - 1);
         let packet = &mut input.packets_mut()[idx];
         self.mutator.mutate_packet(state, packet)
     }
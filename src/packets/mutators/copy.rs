@@ -1,6 +1,6 @@
 use libafl_bolts::prelude::{Named, Rand};
 use libafl::prelude::{Mutator, MutationResult, Error, HasRand};
-use crate::packets::{PacketBasedInput, Packet};
+use crate::packets::{PacketBasedInput, PacketMeta};
 use std::borrow::Cow;
 
 pub struct PacketCopyMutator {
@@ -25,7 +25,7 @@ impl Named for PacketCopyMutator {
 
 impl<P, S> Mutator<PacketBasedInput<P>, S> for PacketCopyMutator
 where
-    P: Packet + Clone,
+    P: PacketMeta + Clone,
     S: HasRand,
 {
     fn mutate(&mut self, state: &mut S, input: &mut PacketBasedInput<P>) -> Result<MutationResult, Error> {
@@ -36,7 +36,9 @@ where
         }
         
         let to = state.rand_mut().between(0, len);
-        let from = state.rand_mut().between(0, len - 1);
+        let Some(from) = input.random_client_packet(state.rand_mut()) else {
+            return Ok(MutationResult::Skipped);
+        };
         
         let packet = input.packets()[from].clone();
         input.packets_mut().insert(to, packet);
@@ -1,6 +1,6 @@
 use libafl_bolts::prelude::{Named, Rand, StdRand};
 use libafl::prelude::{Mutator, MutationResult, Error, HasRand, HasCorpus, random_corpus_id, Corpus};
-use crate::packets::{PacketBasedInput, Packet};
+use crate::packets::{PacketBasedInput, PacketMeta};
 use std::borrow::Cow;
 
 pub struct PacketCrossoverMutator {
@@ -27,7 +27,7 @@ impl Named for PacketCrossoverMutator {
 
 impl<P, S> Mutator<PacketBasedInput<P>, S> for PacketCrossoverMutator
 where
-    P: Packet + Clone,
+    P: PacketMeta + Clone,
     S: HasRand + HasCorpus<PacketBasedInput<P>>,
 {
     fn mutate(&mut self, state: &mut S, input: &mut PacketBasedInput<P>) -> Result<MutationResult, Error> {
@@ -44,11 +44,9 @@ where
         let mut other_testcase = state.corpus().get(idx)?.borrow_mut();
         let other_testcase = other_testcase.load_input(state.corpus())?;
         
-        if other_testcase.packets().is_empty() {
+        let Some(idx) = other_testcase.random_client_packet(&mut self.rand) else {
             return Ok(MutationResult::Skipped);
-        }
-        
-        let idx = self.rand.between(0, other_testcase.packets().len() - 1);
+        };
         let other_packet = &other_testcase.packets()[idx];
         
         let idx = self.rand.between(0, input.packets().len());
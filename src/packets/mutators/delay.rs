@@ -0,0 +1,94 @@
+use libafl_bolts::prelude::{Named, Rand};
+use libafl::prelude::{Mutator, MutationResult, Error, HasRand};
+use crate::packets::{PacketBasedInput, PacketMeta, Timed};
+use std::borrow::Cow;
+use std::time::Duration;
+
+/// Changes the delay of a packet to zero, a tiny value, a value around the receive
+/// timeout of the target or a value well past the timeout.
+/// Delays never grow beyond ten times the timeout.
+pub struct PacketDelayMutator {
+    timeout: Duration,
+}
+
+impl PacketDelayMutator {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+        }
+    }
+}
+
+impl Named for PacketDelayMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("PacketDelayMutator");
+        &NAME
+    }
+}
+
+impl<P, S> Mutator<PacketBasedInput<Timed<P>>, S> for PacketDelayMutator
+where
+    P: PacketMeta,
+    S: HasRand,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut PacketBasedInput<Timed<P>>) -> Result<MutationResult, Error> {
+        let Some(idx) = input.random_client_packet(state.rand_mut()) else {
+            return Ok(MutationResult::Skipped);
+        };
+        
+        let rand = state.rand_mut();
+        let old_delay = input.packets()[idx].delay();
+        let timeout = self.timeout.as_micros() as usize;
+        let max_delay = self.timeout.saturating_mul(10);
+        
+        let new_delay = match rand.between(0, 4) {
+            0 => Duration::ZERO,
+            1 => Duration::from_micros(rand.between(1, 1000) as u64),
+            2 => {
+                let jitter = timeout / 10;
+                let micros = timeout.saturating_sub(jitter) + rand.between(0, 2 * jitter);
+                Duration::from_micros(micros as u64)
+            },
+            3 => self.timeout.saturating_mul(rand.between(2, 10) as u32),
+            4 => {
+                if rand.coinflip(0.5) {
+                    std::cmp::min(old_delay.saturating_mul(2), max_delay)
+                } else {
+                    old_delay / 2
+                }
+            },
+            _ => unreachable!(),
+        };
+        
+        if new_delay == old_delay {
+            return Ok(MutationResult::Skipped);
+        }
+        
+        input.packets_mut()[idx].set_delay(new_delay);
+        Ok(MutationResult::Mutated)
+    }
+    
+    fn post_exec(&mut self, _state: &mut S, _new_corpus_id: Option<libafl::prelude::CorpusId>) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestState;
+    use libafl::prelude::BytesInput;
+    
+    #[test]
+    fn test_large_delays() {
+        let mut state = TestState::new();
+        let mut mutator = PacketDelayMutator::new(Duration::from_secs(1));
+        let mut input = PacketBasedInput::new(vec![Timed::new(Duration::MAX, BytesInput::from(&b"a"[..]))]);
+        
+        for _ in 0..1000 {
+            mutator.mutate(&mut state, &mut input).unwrap();
+        }
+        
+        assert!(input.packets()[0].delay() <= Duration::from_secs(10));
+    }
+}
@@ -1,6 +1,6 @@
-use libafl_bolts::prelude::{Named, Rand};
+use libafl_bolts::prelude::Named;
 use libafl::prelude::{Mutator, MutationResult, Error, HasRand};
-use crate::packets::{PacketBasedInput, Packet};
+use crate::packets::{PacketBasedInput, PacketMeta};
 use std::borrow::Cow;
 
 pub struct PacketDeleteMutator {
@@ -25,17 +25,19 @@ impl Named for PacketDeleteMutator {
 
 impl<P, S> Mutator<PacketBasedInput<P>, S> for PacketDeleteMutator
 where
-    P: Packet,
+    P: PacketMeta,
     S: HasRand,
 {
     fn mutate(&mut self, state: &mut S, input: &mut PacketBasedInput<P>) -> Result<MutationResult, Error> {
-        let len = input.packets().len();
+        let len = input.num_client_packets();
         
         if len == 0 || len <= self.min_length {
             return Ok(MutationResult::Skipped);
         }
         
-        let idx = state.rand_mut().between(0, len - 1);
+        let Some(idx) = input.random_client_packet(state.rand_mut()) else {
+            return Ok(MutationResult::Skipped);
+        };
         input.packets_mut().remove(idx);
         
         Ok(MutationResult::Mutated)
@@ -0,0 +1,111 @@
+use libafl_bolts::prelude::{Named, Rand};
+use libafl::prelude::{Mutator, MutationResult, Error, HasRand};
+use crate::packets::{PacketBasedInput, PacketMeta, Connected};
+use std::borrow::Cow;
+
+/// Moves a packet relative to the packets of other connections. The order of
+/// the packets within each connection stays intact.
+pub struct PacketInterleaveMutator;
+
+impl PacketInterleaveMutator {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Named for PacketInterleaveMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("PacketInterleaveMutator");
+        &NAME
+    }
+}
+
+impl<P, S> Mutator<PacketBasedInput<P>, S> for PacketInterleaveMutator
+where
+    P: PacketMeta,
+    S: HasRand,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut PacketBasedInput<P>) -> Result<MutationResult, Error> {
+        let Some(from) = input.random_client_packet(state.rand_mut()) else {
+            return Ok(MutationResult::Skipped);
+        };
+        
+        let connection = input.packets()[from].connection();
+        let packets = input.packets();
+        let lo = packets[..from].iter().rposition(|p| p.connection() == connection).map_or(0, |i| i + 1);
+        let hi = packets[from + 1..].iter().position(|p| p.connection() == connection).map_or(packets.len(), |i| from + 1 + i);
+        
+        /* After the removal the packet can be inserted anywhere in lo..hi - 1 */
+        if hi - lo <= 1 {
+            return Ok(MutationResult::Skipped);
+        }
+        
+        let to = state.rand_mut().between(lo, hi - 1);
+        
+        if to == from {
+            return Ok(MutationResult::Skipped);
+        }
+        
+        let packet = input.packets_mut().remove(from);
+        input.packets_mut().insert(to, packet);
+        
+        Ok(MutationResult::Mutated)
+    }
+    
+    fn post_exec(&mut self, _state: &mut S, _new_corpus_id: Option<libafl::prelude::CorpusId>) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Moves a packet onto another connection, possibly opening a new one.
+pub struct PacketConnectionMutator {
+    max_connections: usize,
+}
+
+impl PacketConnectionMutator {
+    pub fn new(max_connections: usize) -> Self {
+        Self {
+            max_connections,
+        }
+    }
+}
+
+impl Named for PacketConnectionMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("PacketConnectionMutator");
+        &NAME
+    }
+}
+
+impl<P, S> Mutator<PacketBasedInput<Connected<P>>, S> for PacketConnectionMutator
+where
+    P: PacketMeta,
+    S: HasRand,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut PacketBasedInput<Connected<P>>) -> Result<MutationResult, Error> {
+        if self.max_connections <= 1 {
+            return Ok(MutationResult::Skipped);
+        }
+        
+        let Some(idx) = input.random_client_packet(state.rand_mut()) else {
+            return Ok(MutationResult::Skipped);
+        };
+        
+        /* Prefer connections that already exist over opening new ones */
+        let highest = input.packets().iter().map(|p| p.connection()).max().unwrap_or(0);
+        let limit = std::cmp::min(highest + 1, self.max_connections - 1);
+        let connection = state.rand_mut().between(0, limit);
+        
+        if connection == input.packets()[idx].connection() {
+            return Ok(MutationResult::Skipped);
+        }
+        
+        input.packets_mut()[idx].set_connection(connection);
+        Ok(MutationResult::Mutated)
+    }
+    
+    fn post_exec(&mut self, _state: &mut S, _new_corpus_id: Option<libafl::prelude::CorpusId>) -> Result<(), Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+use libafl_bolts::prelude::{Named, Rand};
+use libafl::prelude::{Mutator, MutationResult, Error, HasRand, BytesInput, HasMutatorBytes, ResizableMutator};
+use crate::packets::{PacketBasedInput, PacketMeta};
+use std::borrow::Cow;
+
+/// Packets that can be joined into a single packet.
+pub trait MergePacket: Sized {
+    /// Appends the content of `other` to this packet.
+    /// Returns `false` and leaves the packet unchanged if the two packets cannot be merged.
+    fn merge_packet(&mut self, other: &Self) -> bool;
+}
+
+impl MergePacket for BytesInput {
+    fn merge_packet(&mut self, other: &Self) -> bool {
+        self.extend(other.mutator_bytes());
+        true
+    }
+}
+
+/// Joins a random run of adjacent client packets into a single packet,
+/// for example to send several commands in one segment.
+/// The merged packet never exceeds `max_size` bytes.
+pub struct PacketMergeMutator {
+    max_size: usize,
+}
+
+impl PacketMergeMutator {
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            max_size,
+        }
+    }
+}
+
+impl Named for PacketMergeMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("PacketMergeMutator");
+        &NAME
+    }
+}
+
+impl<P, S> Mutator<PacketBasedInput<P>, S> for PacketMergeMutator
+where
+    P: PacketMeta + Clone + MergePacket,
+    S: HasRand,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut PacketBasedInput<P>) -> Result<MutationResult, Error> {
+        let packets = input.packets();
+        let starts = packets.windows(2).enumerate().filter(|(_, w)| w[0].is_client() && w[1].is_client()).map(|(idx, _)| idx);
+        
+        let Some(idx) = state.rand_mut().choose(starts) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let run = packets[idx + 1..].iter().take_while(|p| p.is_client()).count();
+        let n = state.rand_mut().between(1, run);
+        let mut merged = packets[idx].clone();
+        let mut count = 0;
+        
+        for other in &packets[idx + 1..=idx + n] {
+            if merged.serialized_len() + other.serialized_len() > self.max_size || !merged.merge_packet(other) {
+                break;
+            }
+            
+            count += 1;
+        }
+        
+        if count == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+        
+        input.packets_mut()[idx] = merged;
+        input.packets_mut().drain(idx + 1..=idx + count);
+        
+        Ok(MutationResult::Mutated)
+    }
+    
+    fn post_exec(&mut self, _state: &mut S, _new_corpus_id: Option<libafl::prelude::CorpusId>) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::Packet;
+    use libafl::prelude::{BytesInput, NopState};
+    
+    #[test]
+    fn test_merge_packets() {
+        let mut state = NopState::<PacketBasedInput<BytesInput>>::new();
+        let packets = vec![
+            BytesInput::from(&b"EHLO a\r\n"[..]),
+            BytesInput::from(&b"MAIL FROM:<b>\r\n"[..]),
+            BytesInput::from(&b"RCPT TO:<c>\r\n"[..]),
+        ];
+        let stream: Vec<u8> = packets.iter().flat_map(|p| p.content_to_vec()).collect();
+        
+        for _ in 0..32 {
+            let mut input = PacketBasedInput::new(packets.clone());
+            assert_eq!(PacketMergeMutator::new(1024).mutate(&mut state, &mut input).unwrap(), MutationResult::Mutated);
+            assert!(input.packets().len() < packets.len());
+            assert_eq!(input.packets().iter().flat_map(|p| p.content_to_vec()).collect::<Vec<u8>>(), stream);
+            
+            let mut input = PacketBasedInput::new(packets.clone());
+            assert_eq!(PacketMergeMutator::new(16).mutate(&mut state, &mut input).unwrap(), MutationResult::Skipped);
+        }
+    }
+}
@@ -6,6 +6,11 @@ mod content;
 mod random;
 mod crossover;
 mod split;
+mod merge;
+mod shift;
+mod delay;
+mod interleave;
+mod prefix;
 
 pub use delete::*;
 pub use copy::*;
@@ -15,3 +20,8 @@ pub use content::*;
 pub use random::*;
 pub use crossover::*;
 pub use split::*;
+pub use merge::*;
+pub use shift::*;
+pub use delay::*;
+pub use interleave::*;
+pub use prefix::*;
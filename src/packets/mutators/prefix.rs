@@ -0,0 +1,185 @@
+use libafl_bolts::prelude::{Named, Rand};
+use libafl::prelude::{Mutator, MutationResult, Error, HasRand, HasCurrentTestcase, HasMetadata, CorpusId};
+use crate::packets::{PacketBasedInput, Packet};
+use crate::states::ScheduledState;
+use std::borrow::Cow;
+
+/// Protects the packets that bring the target into the state that the
+/// [`crate::states::StateScheduler`] picked and lets the inner mutator
+/// only see the packets after them.
+///
+/// The length of the prefix is taken from the [`ScheduledState`] of the current testcase.
+/// Testcases without it are mutated as a whole.
+/// With probability `drop_probability` the suffix is cut at a random position before
+/// it is mutated, so that the packets that follow the mutated region are dropped.
+/// Note that limits of the inner mutators, like the minimum number of packets of the
+/// [`crate::packets::PacketDeleteMutator`], apply to the suffix only.
+/// Use [`PrefixPreservingMutator::set_max_packets`] to bound the length of the whole input.
+pub struct PrefixPreservingMutator<M> {
+    inner: M,
+    drop_probability: f64,
+    max_packets: Option<usize>,
+}
+
+impl<M> PrefixPreservingMutator<M> {
+    pub fn new(inner: M) -> Self {
+        Self {
+            inner,
+            drop_probability: 0.0,
+            max_packets: None,
+        }
+    }
+    
+    pub fn with_drop_probability(inner: M, drop_probability: f64) -> Self {
+        Self {
+            inner,
+            drop_probability,
+            max_packets: None,
+        }
+    }
+    
+    /// Reject mutations of the suffix that make the prefix and the suffix together
+    /// longer than `max_packets` packets.
+    pub fn set_max_packets(&mut self, max_packets: usize) {
+        self.max_packets = Some(max_packets);
+    }
+    
+    pub fn inner(&self) -> &M {
+        &self.inner
+    }
+    
+    pub fn inner_mut(&mut self) -> &mut M {
+        &mut self.inner
+    }
+}
+
+impl<M> Named for PrefixPreservingMutator<M> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("PrefixPreservingMutator");
+        &NAME
+    }
+}
+
+impl<M, P, S> Mutator<PacketBasedInput<P>, S> for PrefixPreservingMutator<M>
+where
+    M: Mutator<PacketBasedInput<P>, S>,
+    P: Packet + Clone,
+    S: HasRand + HasCurrentTestcase<PacketBasedInput<P>>,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut PacketBasedInput<P>) -> Result<MutationResult, Error> {
+        let prefix_len = match state.current_testcase() {
+            Ok(testcase) => testcase.metadata::<ScheduledState>().map(|s| s.packet + 1).unwrap_or(0),
+            Err(_) => 0,
+        };
+        
+        if prefix_len >= input.packets().len() {
+            return Ok(MutationResult::Skipped);
+        }
+        
+        let mut suffix = PacketBasedInput::new(input.packets_mut().split_off(prefix_len));
+        let mut dropped = false;
+        
+        if suffix.packets().len() > 1 && state.rand_mut().coinflip(self.drop_probability) {
+            let len = state.rand_mut().between(1, suffix.packets().len() - 1);
+            suffix.packets_mut().truncate(len);
+            dropped = true;
+        }
+        
+        let original = self.max_packets.map(|_| suffix.packets().to_vec());
+        let mut result = self.inner.mutate(state, &mut suffix);
+        
+        if let Some(max_packets) = self.max_packets
+            && let Some(original) = original
+            && prefix_len + suffix.packets().len() > max_packets
+        {
+            *suffix.packets_mut() = original;
+            result = Ok(MutationResult::Skipped);
+        }
+        
+        input.packets_mut().append(suffix.packets_mut());
+        
+        match result? {
+            MutationResult::Skipped if dropped => Ok(MutationResult::Mutated),
+            result => Ok(result),
+        }
+    }
+    
+    fn post_exec(&mut self, state: &mut S, new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        self.inner.post_exec(state, new_corpus_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::{PacketDeleteMutator, PacketSwapMutator, PacketRepeatMutator};
+    use libafl::prelude::{StdState, InMemoryCorpus, Testcase, BytesInput, HasCorpus, Corpus, HasCurrentCorpusId, HavocScheduledMutator};
+    use libafl_bolts::prelude::{StdRand, tuple_list};
+    
+    #[test]
+    fn test_prefix_untouched() {
+        let input = PacketBasedInput::new(vec![
+            BytesInput::from(&b"USER a\r\n"[..]),
+            BytesInput::from(&b"PASS b\r\n"[..]),
+            BytesInput::from(&b"CWD /\r\n"[..]),
+            BytesInput::from(&b"LIST\r\n"[..]),
+            BytesInput::from(&b"QUIT\r\n"[..]),
+        ]);
+        let mut testcase = Testcase::new(input.clone());
+        testcase.add_metadata(ScheduledState {
+            state: 230,
+            packet: 1,
+        });
+        
+        let mut state = StdState::new(StdRand::with_seed(0), InMemoryCorpus::new(), InMemoryCorpus::new(), &mut (), &mut ()).unwrap();
+        let id = state.corpus_mut().add(testcase).unwrap();
+        state.set_corpus_id(id).unwrap();
+        
+        let mut mutator = PrefixPreservingMutator::with_drop_probability(
+            HavocScheduledMutator::new(tuple_list!(
+                PacketDeleteMutator::new(1),
+                PacketSwapMutator::new(),
+                PacketRepeatMutator::new(16)
+            )),
+            0.1,
+        );
+        
+        for _ in 0..100 {
+            let mut output = input.clone();
+            
+            for _ in 0..8 {
+                mutator.mutate(&mut state, &mut output).unwrap();
+            }
+            
+            assert!(output.packets().len() > 2);
+            assert_eq!(&output.packets()[..2], &input.packets()[..2]);
+        }
+    }
+    
+    #[test]
+    fn test_max_packets() {
+        let input = PacketBasedInput::new(vec![BytesInput::from(&b"NOOP\r\n"[..]); 14]);
+        let mut testcase = Testcase::new(input.clone());
+        testcase.add_metadata(ScheduledState {
+            state: 200,
+            packet: 11,
+        });
+        
+        let mut state = StdState::new(StdRand::with_seed(0), InMemoryCorpus::new(), InMemoryCorpus::new(), &mut (), &mut ()).unwrap();
+        let id = state.corpus_mut().add(testcase).unwrap();
+        state.set_corpus_id(id).unwrap();
+        
+        /* The repeat mutator only sees the 2 packets of the suffix */
+        let mut mutator = PrefixPreservingMutator::new(PacketRepeatMutator::new(16));
+        mutator.set_max_packets(16);
+        let mut mutated = false;
+        
+        for _ in 0..100 {
+            let mut output = input.clone();
+            mutated |= mutator.mutate(&mut state, &mut output).unwrap() == MutationResult::Mutated;
+            assert!(output.packets().len() <= 16);
+        }
+        
+        assert!(mutated);
+    }
+}
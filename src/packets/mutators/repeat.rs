@@ -1,6 +1,6 @@
 use libafl_bolts::prelude::{Named, Rand};
 use libafl::prelude::{Mutator, MutationResult, Error, HasRand};
-use crate::packets::{PacketBasedInput, Packet};
+use crate::packets::{PacketBasedInput, PacketMeta};
 use std::borrow::Cow;
 
 pub struct PacketRepeatMutator {
@@ -25,7 +25,7 @@ impl Named for PacketRepeatMutator {
 
 impl<P, S> Mutator<PacketBasedInput<P>, S> for PacketRepeatMutator
 where
-    P: Packet + Clone,
+    P: PacketMeta + Clone,
     S: HasRand,
 {
     fn mutate(&mut self, state: &mut S, input: &mut PacketBasedInput<P>) -> Result<MutationResult, Error> {
@@ -35,7 +35,9 @@ where
             return Ok(MutationResult::Skipped);
         }
         
-        let idx = state.rand_mut().between(0, len - 1);
+        let Some(idx) = input.random_client_packet(state.rand_mut()) else {
+            return Ok(MutationResult::Skipped);
+        };
         let n = 1 + state.rand_mut().between(0, (self.max_length - len).saturating_sub(1));
         let packet = input.packets()[idx].clone();
         input.packets_mut().splice(idx..idx, vec![packet; n]);
@@ -0,0 +1,137 @@
+use libafl_bolts::prelude::{Named, Rand};
+use libafl::prelude::{Mutator, MutationResult, Error, HasRand, BytesInput, HasMutatorBytes};
+use crate::packets::{PacketBasedInput, PacketMeta};
+use std::borrow::Cow;
+
+/// Packets whose content can be moved across the boundary to the next packet.
+pub trait ShiftPacket: Sized {
+    /// The number of units, like bytes or tokens, that can be moved.
+    fn shift_units(&self) -> usize;
+    
+    /// Redistributes the content of this packet and `next` so that the first `at` units
+    /// of the concatenated content end up in this packet and the rest in `next`.
+    /// Returns `false` and leaves both packets unchanged if this is not possible.
+    fn shift_boundary(&mut self, next: &mut Self, at: usize) -> bool;
+}
+
+impl ShiftPacket for BytesInput {
+    fn shift_units(&self) -> usize {
+        self.mutator_bytes().len()
+    }
+    
+    fn shift_boundary(&mut self, next: &mut Self, at: usize) -> bool {
+        let mut content = self.mutator_bytes().to_vec();
+        content.extend_from_slice(next.mutator_bytes());
+        
+        if at > content.len() {
+            return false;
+        }
+        
+        let tail = content.split_off(at);
+        *self = BytesInput::from(content);
+        *next = BytesInput::from(tail);
+        true
+    }
+}
+
+/// Moves the boundary between two adjacent client packets while the
+/// concatenated content stays the same. This tests how the target handles
+/// messages that arrive in several pieces or together with the next message.
+/// Half of the time the new boundary produces an empty packet or a packet with a single unit.
+pub struct PacketShiftMutator;
+
+impl PacketShiftMutator {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Named for PacketShiftMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("PacketShiftMutator");
+        &NAME
+    }
+}
+
+impl<P, S> Mutator<PacketBasedInput<P>, S> for PacketShiftMutator
+where
+    P: PacketMeta + ShiftPacket,
+    S: HasRand,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut PacketBasedInput<P>) -> Result<MutationResult, Error> {
+        let pairs = input.packets().windows(2).enumerate().filter(|(_, w)| w[0].is_client() && w[1].is_client()).map(|(idx, _)| idx);
+        
+        let Some(idx) = state.rand_mut().choose(pairs) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let (first, second) = input.packets_mut().split_at_mut(idx + 1);
+        let (first, second) = (&mut first[idx], &mut second[0]);
+        let current = first.shift_units();
+        let total = current + second.shift_units();
+        
+        if total == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+        
+        let extremes = [0, 1, total - 1, total].into_iter().filter(|at| *at != current);
+        let at = if state.rand_mut().coinflip(0.5) {
+            state.rand_mut().choose(extremes).unwrap()
+        } else {
+            /* Any boundary except the current one */
+            let at = state.rand_mut().between(0, total - 1);
+            if at >= current { at + 1 } else { at }
+        };
+        
+        if first.shift_boundary(second, at) {
+            Ok(MutationResult::Mutated)
+        } else {
+            Ok(MutationResult::Skipped)
+        }
+    }
+    
+    fn post_exec(&mut self, _state: &mut S, _new_corpus_id: Option<libafl::prelude::CorpusId>) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::Packet;
+    use crate::tokens::TokenStream;
+    use libafl::prelude::NopState;
+    
+    fn content<P: Packet>(input: &PacketBasedInput<P>) -> Vec<u8> {
+        input.packets().iter().flat_map(|p| p.content_to_vec()).collect()
+    }
+    
+    #[test]
+    fn test_shift_boundary() {
+        let mut state = NopState::<PacketBasedInput<BytesInput>>::new();
+        let mut input = PacketBasedInput::new(vec![
+            BytesInput::from(&b"EHLO a\r\n"[..]),
+            BytesInput::from(&b"QUIT\r\n"[..]),
+        ]);
+        let stream = content(&input);
+        let mut empty = false;
+        let mut single = false;
+        
+        for _ in 0..256 {
+            assert_eq!(PacketShiftMutator::new().mutate(&mut state, &mut input).unwrap(), MutationResult::Mutated);
+            assert_eq!(input.packets().len(), 2);
+            assert_eq!(content(&input), stream);
+            empty |= input.packets().iter().any(|p| p.shift_units() == 0);
+            single |= input.packets().iter().any(|p| p.shift_units() == 1);
+        }
+        
+        assert!(empty && single);
+        
+        let mut first = "USER a\r\n".parse::<TokenStream>().unwrap();
+        let mut second = "PASS b\r\n".parse::<TokenStream>().unwrap();
+        assert!(first.shift_boundary(&mut second, 2));
+        assert_eq!(first.content_to_vec(), b"USER ");
+        assert_eq!(second.content_to_vec(), b"a\r\nPASS b\r\n");
+        assert!(!first.shift_boundary(&mut second, 9));
+    }
+}
@@ -2,11 +2,91 @@ use libafl_bolts::prelude::{Named, Rand, HasLen};
 use libafl::prelude::{
     Mutator, MutationResult, Error, HasRand, BytesInput,
 };
-use crate::packets::{PacketBasedInput, Packet};
+use crate::packets::{PacketBasedInput, PacketMeta};
 use std::borrow::Cow;
 
+/// Where a [`PacketSplitMutator`] cuts packets.
+///
+/// The weights decide how often a split happens at a line boundary, at a word boundary
+/// or at a random position. Packets without the requested kind of boundary fall back to the
+/// next weaker one: from lines to words and from words to a random position.
+/// Only packets that know their structure, like `TokenStream`, make use of the boundaries,
+/// all other packets are split at random positions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SplitStrategy {
+    lines: usize,
+    words: usize,
+    random: usize,
+}
+
+/// The kind of boundary that a single split should use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplitBoundary {
+    Line,
+    Word,
+    Random,
+}
+
+impl SplitStrategy {
+    /// Creates a strategy that splits at lines, words and random positions with the given weights.
+    pub fn new(lines: usize, words: usize, random: usize) -> Self {
+        Self {
+            lines,
+            words,
+            random,
+        }
+    }
+    
+    /// Always splits at a random position.
+    pub fn random() -> Self {
+        Self::new(0, 0, 1)
+    }
+    
+    /// Splits after line endings whenever possible.
+    pub fn lines() -> Self {
+        Self::new(1, 0, 0)
+    }
+    
+    /// Splits between words whenever possible.
+    pub fn words() -> Self {
+        Self::new(0, 1, 0)
+    }
+    
+    /// Picks the kind of boundary for the next split according to the weights.
+    pub fn choose<R: Rand>(&self, rand: &mut R) -> SplitBoundary {
+        let total = self.lines.saturating_add(self.words).saturating_add(self.random);
+        
+        if total == 0 {
+            return SplitBoundary::Random;
+        }
+        
+        let pick = rand.below_or_zero(total);
+        
+        if pick < self.lines {
+            SplitBoundary::Line
+        } else if pick < self.lines + self.words {
+            SplitBoundary::Word
+        } else {
+            SplitBoundary::Random
+        }
+    }
+}
+
+impl Default for SplitStrategy {
+    fn default() -> Self {
+        Self::random()
+    }
+}
+
 pub trait SplitPacket<S>: Sized {
     fn split_packet(&mut self, state: &mut S) -> Option<Self>;
+    
+    /// Splits the packet at the given kind of boundary.
+    /// The default ignores the boundary and calls [`SplitPacket::split_packet`].
+    fn split_packet_at(&mut self, state: &mut S, boundary: SplitBoundary) -> Option<Self> {
+        let _ = boundary;
+        self.split_packet(state)
+    }
 }
 
 impl<S> SplitPacket<S> for BytesInput
@@ -25,15 +105,26 @@ where
 
 pub struct PacketSplitMutator {
     max_packets: usize,
+    strategy: SplitStrategy,
 }
 
 impl PacketSplitMutator {
     #[allow(clippy::new_without_default)]
     pub fn new(max_packets: usize) -> Self {
+        Self::with_strategy(max_packets, SplitStrategy::default())
+    }
+    
+    /// Creates a mutator that chooses split positions according to `strategy`.
+    pub fn with_strategy(max_packets: usize, strategy: SplitStrategy) -> Self {
         Self {
             max_packets,
+            strategy,
         }
     }
+    
+    pub fn strategy(&self) -> &SplitStrategy {
+        &self.strategy
+    }
 }
 
 impl Named for PacketSplitMutator {
@@ -45,7 +136,7 @@ impl Named for PacketSplitMutator {
 
 impl<P, S> Mutator<PacketBasedInput<P>, S> for PacketSplitMutator
 where
-    P: Packet + SplitPacket<S>,
+    P: PacketMeta + SplitPacket<S>,
     S: HasRand,
 {
     fn mutate(&mut self, state: &mut S, input: &mut PacketBasedInput<P>) -> Result<MutationResult, Error> {
@@ -55,10 +146,13 @@ where
             return Ok(MutationResult::Skipped);
         }
         
-        let idx = state.rand_mut().between(0, len - 1);
+        let Some(idx) = input.random_client_packet(state.rand_mut()) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let boundary = self.strategy.choose(state.rand_mut());
         let packet = &mut input.packets_mut()[idx];
         
-        if let Some(other_packet) = packet.split_packet(state) {
+        if let Some(other_packet) = packet.split_packet_at(state, boundary) {
             input.packets_mut().insert(idx + 1, other_packet);
             Ok(MutationResult::Mutated)
         } else {
@@ -70,3 +164,33 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::Packet;
+    use crate::tokens::TokenStream;
+    use libafl::prelude::NopState;
+    
+    fn split(text: &str, strategy: SplitStrategy) -> Vec<Vec<u8>> {
+        let mut state = NopState::<PacketBasedInput<TokenStream>>::new();
+        let mut input = PacketBasedInput::new(vec![text.parse::<TokenStream>().unwrap()]);
+        let mut mutator = PacketSplitMutator::with_strategy(16, strategy);
+        assert_eq!(mutator.mutate(&mut state, &mut input).unwrap(), MutationResult::Mutated);
+        input.packets().iter().map(|p| p.content_to_vec()).collect()
+    }
+    
+    #[test]
+    fn test_split_boundaries() {
+        for _ in 0..32 {
+            assert_eq!(split("USER a\r\nPASS b", SplitStrategy::lines()), [b"USER a\r\n".to_vec(), b"PASS b".to_vec()]);
+            
+            /* No line boundaries, so the split falls back to words */
+            let packets = split("MAIL FROM:<a>", SplitStrategy::lines());
+            assert_eq!(packets, [b"MAIL ".to_vec(), b"FROM:<a>".to_vec()]);
+            
+            let packets = split("RCPT TO: b", SplitStrategy::words());
+            assert!(packets == [b"RCPT ".to_vec(), b"TO: b".to_vec()] || packets == [b"RCPT TO: ".to_vec(), b"b".to_vec()]);
+        }
+    }
+}
@@ -1,6 +1,6 @@
-use libafl_bolts::prelude::{Named, Rand};
+use libafl_bolts::prelude::Named;
 use libafl::prelude::{Mutator, MutationResult, Error, HasRand};
-use crate::packets::{PacketBasedInput, Packet};
+use crate::packets::{PacketBasedInput, PacketMeta};
 use std::borrow::Cow;
 
 pub struct PacketSwapMutator;
@@ -21,18 +21,17 @@ impl Named for PacketSwapMutator {
 
 impl<P, S> Mutator<PacketBasedInput<P>, S> for PacketSwapMutator
 where
-    P: Packet,
+    P: PacketMeta,
     S: HasRand,
 {
     fn mutate(&mut self, state: &mut S, input: &mut PacketBasedInput<P>) -> Result<MutationResult, Error> {
-        let len = input.packets().len();
-        
-        if len <= 1 {
+        if input.num_client_packets() <= 1 {
             return Ok(MutationResult::Skipped);
         }
         
-        let to = state.rand_mut().between(0, len - 1);
-        let from = state.rand_mut().between(0, len - 1);
+        let (Some(to), Some(from)) = (input.random_client_packet(state.rand_mut()), input.random_client_packet(state.rand_mut())) else {
+            return Ok(MutationResult::Skipped);
+        };
         
         if to == from {
             return Ok(MutationResult::Skipped);
@@ -0,0 +1,858 @@
+use crate::packets::{Packet, PacketMeta, PacketBasedInput};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+const PCAP_MAGIC_US: u32 = 0xa1b2c3d4;
+const PCAP_MAGIC_NS: u32 = 0xa1b23c4d;
+const PCAPNG_SHB: u32 = 0x0a0d0d0a;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b3c4d;
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LOOP: u32 = 108;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+
+const TCP_FIN: u8 = 0x01;
+const TCP_SYN: u8 = 0x02;
+const TCP_ACK: u8 = 0x10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Transport {
+    Tcp,
+    Udp,
+}
+
+/// The 5-tuple of a conversation in a capture, oriented from client to server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Flow {
+    pub transport: Transport,
+    pub client: SocketAddr,
+    pub server: SocketAddr,
+}
+
+impl Flow {
+    pub fn new(transport: Transport, client: SocketAddr, server: SocketAddr) -> Self {
+        Self {
+            transport,
+            client,
+            server,
+        }
+    }
+    
+    fn reversed(&self) -> Self {
+        Self {
+            transport: self.transport,
+            client: self.server,
+            server: self.client,
+        }
+    }
+}
+
+/// How the client data of a flow is cut into packets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum PcapSegmentation {
+    /// One packet per TCP segment or UDP datagram.
+    Segment,
+    
+    /// Consecutive TCP segments of the client are merged until the server sends data.
+    /// UDP datagrams are always taken as they are.
+    #[default]
+    Message,
+}
+
+/// A transport layer payload extracted from a capture.
+struct Datagram {
+    flow: Flow,
+    seq: u32,
+    flags: u8,
+    payload: Vec<u8>,
+}
+
+/// Reads the client→server direction of a single conversation from a pcap or pcapng capture.
+#[derive(Clone, Debug, Default)]
+pub struct PcapReader {
+    flow: Option<Flow>,
+    segmentation: PcapSegmentation,
+}
+
+impl PcapReader {
+    /// Take the first conversation of the capture that carries client data.
+    pub fn new(segmentation: PcapSegmentation) -> Self {
+        Self {
+            flow: None,
+            segmentation,
+        }
+    }
+    
+    /// Take the conversation with the given 5-tuple.
+    pub fn with_flow(flow: Flow, segmentation: PcapSegmentation) -> Self {
+        Self {
+            flow: Some(flow),
+            segmentation,
+        }
+    }
+    
+    /// Returns the payloads that the client sent in the selected conversation.
+    pub fn read_payloads(&self, buf: &[u8]) -> Option<Vec<Vec<u8>>> {
+        let datagrams = parse_capture(buf)?;
+        let flow = match self.flow {
+            Some(flow) => flow,
+            None => select_flow(&datagrams)?,
+        };
+        let reverse = flow.reversed();
+        let conversation = datagrams.into_iter().filter(|d| d.flow == flow || d.flow == reverse);
+        
+        match flow.transport {
+            Transport::Udp => Some(
+                conversation
+                    .filter(|d| d.flow == flow && !d.payload.is_empty())
+                    .map(|d| d.payload)
+                    .collect()
+            ),
+            Transport::Tcp => Some(reassemble_tcp(conversation.collect(), &flow, self.segmentation)),
+        }
+    }
+    
+    pub fn read<P: Packet>(&self, buf: &[u8]) -> Option<PacketBasedInput<P>> {
+        let mut packets = vec![];
+        
+        for payload in self.read_payloads(buf)? {
+            packets.push(P::deserialize_content(&payload)?);
+        }
+        
+        Some(PacketBasedInput::new(packets))
+    }
+}
+
+/// The client of a flow is whoever sent the initial SYN or, failing that, the first payload.
+fn select_flow(datagrams: &[Datagram]) -> Option<Flow> {
+    let mut candidates: Vec<Flow> = vec![];
+    
+    for d in datagrams {
+        let reverse = d.flow.reversed();
+        
+        if candidates.iter().any(|c| *c == d.flow || *c == reverse) {
+            continue;
+        }
+        
+        if d.flow.transport == Transport::Tcp && d.flags & (TCP_SYN | TCP_ACK) == TCP_SYN {
+            candidates.push(d.flow);
+        } else if d.flow.transport == Transport::Tcp && d.flags & (TCP_SYN | TCP_ACK) == TCP_SYN | TCP_ACK {
+            candidates.push(reverse);
+        } else if !d.payload.is_empty() {
+            candidates.push(d.flow);
+        }
+    }
+    
+    candidates.into_iter().find(|flow| datagrams.iter().any(|d| d.flow == *flow && !d.payload.is_empty()))
+}
+
+/* The parts of [start, end) that are not covered yet. `covered` is sorted and its intervals are disjoint. */
+fn uncovered(covered: &[(u32, u32)], start: u32, end: u32) -> Vec<(u32, u32)> {
+    let mut gaps = vec![];
+    let mut cursor = start;
+    
+    for &(s, e) in covered {
+        if e <= cursor {
+            continue;
+        }
+        
+        if s >= end {
+            break;
+        }
+        
+        if s > cursor {
+            gaps.push((cursor, s));
+        }
+        
+        cursor = e;
+    }
+    
+    if cursor < end {
+        gaps.push((cursor, end));
+    }
+    
+    gaps
+}
+
+/* Adds [start, end) to the coverage and merges it with adjacent intervals */
+fn cover(covered: &mut Vec<(u32, u32)>, start: u32, end: u32) {
+    let idx = covered.partition_point(|&(s, _)| s < start);
+    covered.insert(idx, (start, end));
+    
+    let mut merged: Vec<(u32, u32)> = Vec::with_capacity(covered.len());
+    
+    for &(s, e) in covered.iter() {
+        match merged.last_mut() {
+            Some(last) if s <= last.1 => last.1 = std::cmp::max(last.1, e),
+            _ => merged.push((s, e)),
+        }
+    }
+    
+    *covered = merged;
+}
+
+fn reassemble_tcp(conversation: Vec<Datagram>, flow: &Flow, segmentation: PcapSegmentation) -> Vec<Vec<u8>> {
+    let client_isn = conversation.iter()
+        .find(|d| d.flow == *flow && d.flags & TCP_SYN != 0)
+        .map(|d| d.seq.wrapping_add(1))
+        .or_else(|| conversation.iter().find(|d| d.flow == *flow && !d.payload.is_empty()).map(|d| d.seq));
+    let Some(client_isn) = client_isn else {
+        return Vec::new();
+    };
+    
+    /* Drop retransmissions and trim overlaps on both ends so that every byte of the stream is taken once,
+       regardless of the order in which the segments were captured */
+    let mut covered: Vec<(u32, u32)> = vec![];
+    let mut segments: Vec<(u32, Vec<u8>, bool)> = vec![];
+    let mut server_spoke = false;
+    
+    for d in conversation {
+        if d.payload.is_empty() {
+            continue;
+        }
+        
+        if d.flow != *flow {
+            server_spoke = true;
+            continue;
+        }
+        
+        let start = d.seq.wrapping_sub(client_isn);
+        let end = start.wrapping_add(d.payload.len() as u32);
+        
+        for (s, e) in uncovered(&covered, start, end) {
+            let payload = d.payload[(s - start) as usize..(e - start) as usize].to_vec();
+            cover(&mut covered, s, e);
+            segments.push((s, payload, server_spoke));
+            server_spoke = false;
+        }
+    }
+    
+    /* Out-of-order segments are put back into stream order */
+    segments.sort_by_key(|(offset, _, _)| *offset);
+    
+    match segmentation {
+        PcapSegmentation::Segment => segments.into_iter().map(|(_, payload, _)| payload).collect(),
+        PcapSegmentation::Message => {
+            let mut messages: Vec<Vec<u8>> = vec![];
+            
+            for (_, payload, after_response) in segments {
+                match messages.last_mut() {
+                    Some(last) if !after_response => last.extend_from_slice(&payload),
+                    _ => messages.push(payload),
+                }
+            }
+            
+            messages
+        },
+    }
+}
+
+struct Cursor<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self {
+            buf,
+            pos: 0,
+            big_endian: false,
+        }
+    }
+    
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let data = self.buf.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(data)
+    }
+    
+    fn u16(&mut self) -> Option<u16> {
+        let data: [u8; 2] = self.bytes(2)?.try_into().ok()?;
+        Some(if self.big_endian { u16::from_be_bytes(data) } else { u16::from_le_bytes(data) })
+    }
+    
+    fn u32(&mut self) -> Option<u32> {
+        let data: [u8; 4] = self.bytes(4)?.try_into().ok()?;
+        Some(if self.big_endian { u32::from_be_bytes(data) } else { u32::from_le_bytes(data) })
+    }
+    
+    fn is_empty(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+}
+
+fn parse_capture(buf: &[u8]) -> Option<Vec<Datagram>> {
+    let magic = u32::from_le_bytes(buf.get(0..4)?.try_into().ok()?);
+    
+    if magic == PCAPNG_SHB {
+        parse_pcapng(buf)
+    } else {
+        parse_pcap(buf)
+    }
+}
+
+fn parse_pcap(buf: &[u8]) -> Option<Vec<Datagram>> {
+    let mut cursor = Cursor::new(buf);
+    
+    match cursor.u32()? {
+        PCAP_MAGIC_US | PCAP_MAGIC_NS => {},
+        magic if magic.swap_bytes() == PCAP_MAGIC_US || magic.swap_bytes() == PCAP_MAGIC_NS => cursor.big_endian = true,
+        _ => return None,
+    }
+    
+    /* version, thiszone, sigfigs, snaplen */
+    cursor.bytes(16)?;
+    let linktype = cursor.u32()? & 0x0fffffff;
+    let mut datagrams = vec![];
+    
+    while !cursor.is_empty() {
+        /* ts_sec, ts_frac */
+        cursor.bytes(8)?;
+        let incl_len = cursor.u32()? as usize;
+        let _orig_len = cursor.u32()?;
+        let frame = cursor.bytes(incl_len)?;
+        
+        if let Some(datagram) = parse_frame(linktype, frame) {
+            datagrams.push(datagram);
+        }
+    }
+    
+    Some(datagrams)
+}
+
+fn parse_pcapng(buf: &[u8]) -> Option<Vec<Datagram>> {
+    let mut cursor = Cursor::new(buf);
+    let mut linktypes = vec![];
+    let mut datagrams = vec![];
+    
+    while !cursor.is_empty() {
+        let start = cursor.pos;
+        let block_type = cursor.u32()?;
+        
+        if block_type == PCAPNG_SHB {
+            /* The byte order magic follows the length, so peek at it to find out how to read the length */
+            let bom = cursor.buf.get(start + 8..start + 12)?;
+            
+            if u32::from_le_bytes(bom.try_into().ok()?) == PCAPNG_BYTE_ORDER_MAGIC {
+                cursor.big_endian = false;
+            } else if u32::from_be_bytes(bom.try_into().ok()?) == PCAPNG_BYTE_ORDER_MAGIC {
+                cursor.big_endian = true;
+            } else {
+                return None;
+            }
+            
+            linktypes.clear();
+        }
+        
+        let block_len = cursor.u32()? as usize;
+        
+        if block_len < 12 || !block_len.is_multiple_of(4) {
+            return None;
+        }
+        
+        let mut body = Cursor::new(cursor.bytes(block_len - 12)?);
+        body.big_endian = cursor.big_endian;
+        cursor.u32()?;
+        
+        match block_type {
+            /* Interface Description Block */
+            1 => linktypes.push(body.u16()? as u32),
+            /* Enhanced Packet Block */
+            6 => {
+                let interface = body.u32()? as usize;
+                body.bytes(8)?;
+                let captured_len = body.u32()? as usize;
+                let _orig_len = body.u32()?;
+                let frame = body.bytes(captured_len)?;
+                
+                if let Some(datagram) = parse_frame(*linktypes.get(interface)?, frame) {
+                    datagrams.push(datagram);
+                }
+            },
+            /* Simple Packet Block */
+            3 => {
+                let orig_len = body.u32()? as usize;
+                let frame = &body.buf[body.pos..];
+                let frame = &frame[..std::cmp::min(orig_len, frame.len())];
+                
+                if let Some(datagram) = parse_frame(*linktypes.first()?, frame) {
+                    datagrams.push(datagram);
+                }
+            },
+            /* Obsolete Packet Block */
+            2 => {
+                let interface = body.u16()? as usize;
+                body.bytes(10)?;
+                let captured_len = body.u32()? as usize;
+                let _orig_len = body.u32()?;
+                let frame = body.bytes(captured_len)?;
+                
+                if let Some(datagram) = parse_frame(*linktypes.get(interface)?, frame) {
+                    datagrams.push(datagram);
+                }
+            },
+            _ => {},
+        }
+    }
+    
+    Some(datagrams)
+}
+
+fn parse_frame(linktype: u32, frame: &[u8]) -> Option<Datagram> {
+    match linktype {
+        LINKTYPE_ETHERNET => {
+            let mut offset = 12;
+            let mut ethertype = u16::from_be_bytes(frame.get(offset..offset + 2)?.try_into().ok()?);
+            
+            /* Skip VLAN tags */
+            while ethertype == 0x8100 || ethertype == 0x88a8 {
+                offset += 4;
+                ethertype = u16::from_be_bytes(frame.get(offset..offset + 2)?.try_into().ok()?);
+            }
+            
+            parse_ethertype(ethertype, frame.get(offset + 2..)?)
+        },
+        LINKTYPE_NULL | LINKTYPE_LOOP => parse_ip(frame.get(4..)?),
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => parse_ip(frame),
+        LINKTYPE_LINUX_SLL => {
+            let ethertype = u16::from_be_bytes(frame.get(14..16)?.try_into().ok()?);
+            parse_ethertype(ethertype, frame.get(16..)?)
+        },
+        LINKTYPE_LINUX_SLL2 => {
+            let ethertype = u16::from_be_bytes(frame.get(0..2)?.try_into().ok()?);
+            parse_ethertype(ethertype, frame.get(20..)?)
+        },
+        _ => None,
+    }
+}
+
+fn parse_ethertype(ethertype: u16, data: &[u8]) -> Option<Datagram> {
+    match ethertype {
+        0x0800 | 0x86dd => parse_ip(data),
+        _ => None,
+    }
+}
+
+fn parse_ip(data: &[u8]) -> Option<Datagram> {
+    match data.first()? >> 4 {
+        4 => {
+            let ihl = ((data[0] & 0x0f) as usize) * 4;
+            let total_len = u16::from_be_bytes(data.get(2..4)?.try_into().ok()?) as usize;
+            let fragment = u16::from_be_bytes(data.get(6..8)?.try_into().ok()?);
+            
+            /* Fragmented datagrams are not reassembled */
+            if fragment & 0x3fff != 0 {
+                return None;
+            }
+            
+            let protocol = *data.get(9)?;
+            let src = Ipv4Addr::from(<[u8; 4]>::try_from(data.get(12..16)?).ok()?);
+            let dst = Ipv4Addr::from(<[u8; 4]>::try_from(data.get(16..20)?).ok()?);
+            let end = std::cmp::min(std::cmp::max(total_len, ihl), data.len());
+            parse_transport(protocol, IpAddr::V4(src), IpAddr::V4(dst), data.get(ihl..end)?)
+        },
+        6 => {
+            let payload_len = u16::from_be_bytes(data.get(4..6)?.try_into().ok()?) as usize;
+            let protocol = *data.get(6)?;
+            let src = Ipv6Addr::from(<[u8; 16]>::try_from(data.get(8..24)?).ok()?);
+            let dst = Ipv6Addr::from(<[u8; 16]>::try_from(data.get(24..40)?).ok()?);
+            let end = std::cmp::min(40 + payload_len, data.len());
+            parse_transport(protocol, IpAddr::V6(src), IpAddr::V6(dst), data.get(40..end)?)
+        },
+        _ => None,
+    }
+}
+
+fn parse_transport(protocol: u8, src: IpAddr, dst: IpAddr, data: &[u8]) -> Option<Datagram> {
+    let src_port = u16::from_be_bytes(data.get(0..2)?.try_into().ok()?);
+    let dst_port = u16::from_be_bytes(data.get(2..4)?.try_into().ok()?);
+    let client = SocketAddr::new(src, src_port);
+    let server = SocketAddr::new(dst, dst_port);
+    
+    match protocol {
+        IPPROTO_TCP => {
+            let seq = u32::from_be_bytes(data.get(4..8)?.try_into().ok()?);
+            let data_offset = ((*data.get(12)? >> 4) as usize) * 4;
+            let flags = *data.get(13)?;
+            let payload = data.get(data_offset..)?.to_vec();
+            
+            Some(Datagram {
+                flow: Flow::new(Transport::Tcp, client, server),
+                seq,
+                flags: flags & (TCP_FIN | TCP_SYN | TCP_ACK),
+                payload,
+            })
+        },
+        IPPROTO_UDP => {
+            let len = u16::from_be_bytes(data.get(4..6)?.try_into().ok()?) as usize;
+            let end = std::cmp::min(std::cmp::max(len, 8), data.len());
+            
+            Some(Datagram {
+                flow: Flow::new(Transport::Udp, client, server),
+                seq: 0,
+                flags: 0,
+                payload: data.get(8..end)?.to_vec(),
+            })
+        },
+        _ => None,
+    }
+}
+
+/// The largest payload that fits into a single IPv4 datagram together with a TCP header.
+const MAX_PAYLOAD: usize = 65535 - 20 - 20;
+
+const TCP_PSH: u8 = 0x08;
+
+/* Payloads that don't fit into a single datagram are split into several ones, empty payloads are kept */
+fn split_payload(payload: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let count = std::cmp::max(payload.len().div_ceil(MAX_PAYLOAD), 1);
+    (0..count).map(move |i| &payload[i * MAX_PAYLOAD..std::cmp::min((i + 1) * MAX_PAYLOAD, payload.len())])
+}
+
+/// Writes packets as a synthetic conversation between two made-up endpoints so that
+/// they can be inspected with the protocol dissectors of Wireshark.
+#[derive(Clone, Debug)]
+pub struct PcapWriter {
+    flow: Flow,
+}
+
+impl Default for PcapWriter {
+    fn default() -> Self {
+        Self::new(Transport::Tcp, 8000)
+    }
+}
+
+impl PcapWriter {
+    /// Use the endpoints 10.0.0.1:40000 (client) and 10.0.0.2:`port` (server).
+    pub fn new(transport: Transport, port: u16) -> Self {
+        Self {
+            flow: Flow::new(
+                transport,
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 40000),
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), port),
+            ),
+        }
+    }
+    
+    pub fn with_flow(flow: Flow) -> Self {
+        Self {
+            flow,
+        }
+    }
+    
+    /// Writes the payloads as a pcap file. TCP conversations get a handshake, one segment
+    /// per payload with valid sequence numbers and a teardown.
+    /// Payloads larger than a single datagram are split into several segments or datagrams.
+    pub fn write_payloads<'a, I>(&self, payloads: I) -> Vec<u8>
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+    {
+        self.write_conversation(payloads.into_iter().map(|p| (true, p)))
+    }
+    
+    /// Like [`Self::write_payloads`] but every payload is tagged with whether the client sent it.
+    pub fn write_conversation<'a, I>(&self, payloads: I) -> Vec<u8>
+    where
+        I: IntoIterator<Item = (bool, &'a [u8])>,
+    {
+        let mut buf = vec![];
+        buf.extend_from_slice(&PCAP_MAGIC_US.to_le_bytes());
+        /* version 2.4, thiszone, sigfigs */
+        buf.extend_from_slice(&[2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        buf.extend_from_slice(&(MAX_PAYLOAD as u32 + 128).to_le_bytes());
+        buf.extend_from_slice(&LINKTYPE_ETHERNET.to_le_bytes());
+        
+        let client = self.flow;
+        let server = self.flow.reversed();
+        let mut frames = vec![];
+        
+        match self.flow.transport {
+            Transport::Udp => {
+                for (from_client, payload) in payloads {
+                    for payload in split_payload(payload) {
+                        frames.push(build_frame(if from_client { &client } else { &server }, 0, 0, 0, payload));
+                    }
+                }
+            },
+            Transport::Tcp => {
+                let mut client_seq: u32 = 1000;
+                let mut server_seq: u32 = 5000;
+                
+                frames.push(build_frame(&client, client_seq, 0, TCP_SYN, b""));
+                frames.push(build_frame(&server, server_seq, client_seq + 1, TCP_SYN | TCP_ACK, b""));
+                client_seq += 1;
+                server_seq += 1;
+                frames.push(build_frame(&client, client_seq, server_seq, TCP_ACK, b""));
+                
+                for (from_client, payload) in payloads {
+                    for payload in split_payload(payload) {
+                        if from_client {
+                            frames.push(build_frame(&client, client_seq, server_seq, TCP_PSH | TCP_ACK, payload));
+                            client_seq = client_seq.wrapping_add(payload.len() as u32);
+                            frames.push(build_frame(&server, server_seq, client_seq, TCP_ACK, b""));
+                        } else {
+                            frames.push(build_frame(&server, server_seq, client_seq, TCP_PSH | TCP_ACK, payload));
+                            server_seq = server_seq.wrapping_add(payload.len() as u32);
+                            frames.push(build_frame(&client, client_seq, server_seq, TCP_ACK, b""));
+                        }
+                    }
+                }
+                
+                frames.push(build_frame(&client, client_seq, server_seq, TCP_FIN | TCP_ACK, b""));
+                frames.push(build_frame(&server, server_seq, client_seq.wrapping_add(1), TCP_FIN | TCP_ACK, b""));
+                frames.push(build_frame(&client, client_seq.wrapping_add(1), server_seq.wrapping_add(1), TCP_ACK, b""));
+            },
+        }
+        
+        for (i, frame) in frames.iter().enumerate() {
+            /* One millisecond between frames */
+            buf.extend_from_slice(&((i / 1000) as u32).to_le_bytes());
+            buf.extend_from_slice(&(((i % 1000) * 1000) as u32).to_le_bytes());
+            buf.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            buf.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            buf.extend_from_slice(frame);
+        }
+        
+        buf
+    }
+    
+    /// Client packets are written in the client→server direction and all other packets
+    /// in the server→client direction.
+    pub fn write<P: PacketMeta>(&self, input: &PacketBasedInput<P>) -> Vec<u8> {
+        let payloads: Vec<(bool, Vec<u8>)> = input.packets().iter().map(|p| (p.is_client(), p.content_to_vec())).collect();
+        self.write_conversation(payloads.iter().map(|(from_client, p)| (*from_client, &p[..])))
+    }
+}
+
+fn checksum(chunks: &[&[u8]]) -> u16 {
+    let mut sum = 0u32;
+    
+    for chunk in chunks {
+        for word in chunk.chunks(2) {
+            let hi = word[0] as u32;
+            let lo = word.get(1).copied().unwrap_or(0) as u32;
+            sum += (hi << 8) | lo;
+        }
+    }
+    
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    
+    !(sum as u16)
+}
+
+fn build_frame(flow: &Flow, seq: u32, ack: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+    let (src, dst) = match (flow.client.ip(), flow.server.ip()) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => (IpAddr::V4(src), IpAddr::V4(dst)),
+        (src, dst) => (IpAddr::V6(to_ipv6(src)), IpAddr::V6(to_ipv6(dst))),
+    };
+    
+    let mut segment = vec![];
+    segment.extend_from_slice(&flow.client.port().to_be_bytes());
+    segment.extend_from_slice(&flow.server.port().to_be_bytes());
+    
+    let protocol = match flow.transport {
+        Transport::Tcp => {
+            segment.extend_from_slice(&seq.to_be_bytes());
+            segment.extend_from_slice(&ack.to_be_bytes());
+            segment.extend_from_slice(&[0x50, flags]);
+            /* window, checksum, urgent pointer */
+            segment.extend_from_slice(&[0xff, 0xff, 0, 0, 0, 0]);
+            IPPROTO_TCP
+        },
+        Transport::Udp => {
+            segment.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+            segment.extend_from_slice(&[0, 0]);
+            IPPROTO_UDP
+        },
+    };
+    segment.extend_from_slice(payload);
+    
+    let checksum_offset = if protocol == IPPROTO_TCP { 16 } else { 6 };
+    let segment_len = segment.len() as u32;
+    let mut frame = vec![
+        /* dst mac */
+        0x02, 0, 0, 0, 0, 0x02,
+        /* src mac */
+        0x02, 0, 0, 0, 0, 0x01,
+    ];
+    
+    match (src, dst) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            let pseudo = [&src.octets()[..], &dst.octets(), &[0, protocol], &(segment_len as u16).to_be_bytes()].concat();
+            let sum = checksum(&[&pseudo, &segment]);
+            segment[checksum_offset..checksum_offset + 2].copy_from_slice(&sum.to_be_bytes());
+            
+            let mut header = vec![0x45, 0];
+            header.extend_from_slice(&((20 + segment_len) as u16).to_be_bytes());
+            /* id, don't fragment, ttl */
+            header.extend_from_slice(&[0, 0, 0x40, 0, 64, protocol, 0, 0]);
+            header.extend_from_slice(&src.octets());
+            header.extend_from_slice(&dst.octets());
+            let sum = checksum(&[&header]);
+            header[10..12].copy_from_slice(&sum.to_be_bytes());
+            
+            frame.extend_from_slice(&0x0800u16.to_be_bytes());
+            frame.extend_from_slice(&header);
+        },
+        (IpAddr::V6(src), IpAddr::V6(dst)) => {
+            let pseudo = [&src.octets()[..], &dst.octets(), &segment_len.to_be_bytes(), &[0, 0, 0, protocol]].concat();
+            let sum = checksum(&[&pseudo, &segment]);
+            segment[checksum_offset..checksum_offset + 2].copy_from_slice(&sum.to_be_bytes());
+            
+            frame.extend_from_slice(&0x86ddu16.to_be_bytes());
+            frame.extend_from_slice(&[0x60, 0, 0, 0]);
+            frame.extend_from_slice(&(segment_len as u16).to_be_bytes());
+            frame.extend_from_slice(&[protocol, 64]);
+            frame.extend_from_slice(&src.octets());
+            frame.extend_from_slice(&dst.octets());
+        },
+        _ => unreachable!(),
+    }
+    
+    frame.extend_from_slice(&segment);
+    frame
+}
+
+fn to_ipv6(addr: IpAddr) -> Ipv6Addr {
+    match addr {
+        IpAddr::V4(addr) => addr.to_ipv6_mapped(),
+        IpAddr::V6(addr) => addr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libafl::prelude::BytesInput;
+    
+    fn tcp_frame(src: [u8; 4], sport: u16, dst: [u8; 4], dport: u16, seq: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&0x0800u16.to_be_bytes());
+        frame.extend_from_slice(&[0x45, 0]);
+        frame.extend_from_slice(&((40 + payload.len()) as u16).to_be_bytes());
+        frame.extend_from_slice(&[0, 0, 0x40, 0, 64, IPPROTO_TCP, 0, 0]);
+        frame.extend_from_slice(&src);
+        frame.extend_from_slice(&dst);
+        frame.extend_from_slice(&sport.to_be_bytes());
+        frame.extend_from_slice(&dport.to_be_bytes());
+        frame.extend_from_slice(&seq.to_be_bytes());
+        frame.extend_from_slice(&[0, 0, 0, 0, 0x50, flags, 0xff, 0xff, 0, 0, 0, 0]);
+        frame.extend_from_slice(payload);
+        frame
+    }
+    
+    fn pcap(frames: &[Vec<u8>]) -> Vec<u8> {
+        let mut buf = vec![];
+        buf.extend_from_slice(&PCAP_MAGIC_US.to_le_bytes());
+        buf.extend_from_slice(&[2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0, 0]);
+        buf.extend_from_slice(&LINKTYPE_ETHERNET.to_le_bytes());
+        
+        for frame in frames {
+            buf.extend_from_slice(&[0; 8]);
+            buf.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            buf.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            buf.extend_from_slice(frame);
+        }
+        
+        buf
+    }
+    
+    #[test]
+    fn test_export() {
+        let input = PacketBasedInput::<BytesInput>::new(vec![
+            BytesInput::from(&b"USER a\r\n"[..]),
+            BytesInput::from(&b""[..]),
+            BytesInput::from(&b"PASS b\r\n"[..]),
+        ]);
+        
+        for transport in [Transport::Tcp, Transport::Udp] {
+            let capture = PcapWriter::new(transport, 21).write(&input);
+            
+            /* The first frame starts after the global header and the record header */
+            let frame = &capture[24 + 16..];
+            assert_eq!(checksum(&[&frame[14..34]]), 0);
+            
+            let output = PcapReader::new(PcapSegmentation::Segment).read::<BytesInput>(&capture).unwrap();
+            let packets: Vec<&[u8]> = output.packets().iter().map(|p| &p.as_ref()[..]).collect();
+            assert_eq!(packets, [&b"USER a\r\n"[..], b"PASS b\r\n"]);
+        }
+    }
+    
+    #[test]
+    fn test_tcp_reassembly() {
+        const CLIENT: [u8; 4] = [10, 0, 0, 1];
+        const SERVER: [u8; 4] = [10, 0, 0, 2];
+        let frames = [
+            tcp_frame(CLIENT, 40000, SERVER, 21, 100, TCP_SYN, b""),
+            tcp_frame(SERVER, 21, CLIENT, 40000, 500, TCP_SYN | TCP_ACK, b""),
+            tcp_frame(SERVER, 21, CLIENT, 40000, 501, TCP_ACK, b"220 hi\r\n"),
+            tcp_frame(CLIENT, 40000, SERVER, 21, 101, TCP_ACK, b"USER "),
+            tcp_frame(CLIENT, 40000, SERVER, 21, 106, TCP_ACK, b"a\r\n"),
+            /* retransmission */
+            tcp_frame(CLIENT, 40000, SERVER, 21, 106, TCP_ACK, b"a\r\n"),
+            tcp_frame(SERVER, 21, CLIENT, 40000, 509, TCP_ACK, b"331 ok\r\n"),
+            tcp_frame(CLIENT, 40000, SERVER, 21, 109, TCP_ACK, b"PASS b\r\n"),
+        ];
+        let capture = pcap(&frames);
+        
+        let input = PcapReader::new(PcapSegmentation::Segment).read::<BytesInput>(&capture).unwrap();
+        let packets: Vec<&[u8]> = input.packets().iter().map(|p| &p.as_ref()[..]).collect();
+        assert_eq!(packets, [&b"USER "[..], b"a\r\n", b"PASS b\r\n"]);
+        
+        let input = PcapReader::new(PcapSegmentation::Message).read::<BytesInput>(&capture).unwrap();
+        let packets: Vec<&[u8]> = input.packets().iter().map(|p| &p.as_ref()[..]).collect();
+        assert_eq!(packets, [&b"USER a\r\n"[..], b"PASS b\r\n"]);
+    }
+    
+    #[test]
+    fn test_tcp_overlap() {
+        const CLIENT: [u8; 4] = [10, 0, 0, 1];
+        const SERVER: [u8; 4] = [10, 0, 0, 2];
+        let frames = [
+            tcp_frame(CLIENT, 40000, SERVER, 21, 100, TCP_SYN, b""),
+            /* out of order, the tails of the next two segments run into covered data */
+            tcp_frame(CLIENT, 40000, SERVER, 21, 109, TCP_ACK, b"PASS b\r\n"),
+            tcp_frame(CLIENT, 40000, SERVER, 21, 106, TCP_ACK, b"a\r\nP"),
+            tcp_frame(CLIENT, 40000, SERVER, 21, 101, TCP_ACK, b"USER a"),
+            /* retransmissions that overlap both neighbours */
+            tcp_frame(CLIENT, 40000, SERVER, 21, 104, TCP_ACK, b"R a\r\nPA"),
+            tcp_frame(CLIENT, 40000, SERVER, 21, 101, TCP_ACK, b"USER a\r\nPASS b\r\nQUIT\r\n"),
+        ];
+        let capture = pcap(&frames);
+        
+        let input = PcapReader::new(PcapSegmentation::Segment).read::<BytesInput>(&capture).unwrap();
+        let packets: Vec<&[u8]> = input.packets().iter().map(|p| &p.as_ref()[..]).collect();
+        assert_eq!(packets, [&b"USER "[..], b"a\r\n", b"PASS b\r\n", b"QUIT\r\n"]);
+        
+        let input = PcapReader::new(PcapSegmentation::Message).read::<BytesInput>(&capture).unwrap();
+        let packets: Vec<&[u8]> = input.packets().iter().map(|p| &p.as_ref()[..]).collect();
+        assert_eq!(packets, [&b"USER a\r\nPASS b\r\nQUIT\r\n"[..]]);
+    }
+    
+    #[test]
+    fn test_export_large() {
+        let payload: Vec<u8> = (0..150_000).map(|i| i as u8).collect();
+        let input = PacketBasedInput::<BytesInput>::new(vec![BytesInput::from(&payload[..])]);
+        
+        let capture = PcapWriter::new(Transport::Tcp, 21).write(&input);
+        let output = PcapReader::new(PcapSegmentation::Message).read::<BytesInput>(&capture).unwrap();
+        assert_eq!(output.packets().len(), 1);
+        assert_eq!(output.packets()[0].as_ref(), &payload[..]);
+        
+        let capture = PcapWriter::new(Transport::Udp, 21).write(&input);
+        let output = PcapReader::new(PcapSegmentation::Segment).read::<BytesInput>(&capture).unwrap();
+        assert_eq!(output.packets().len(), 3);
+        assert_eq!(output.packets().iter().flat_map(|p| p.as_ref().iter().copied()).collect::<Vec<u8>>(), payload);
+    }
+}
@@ -0,0 +1,175 @@
+use libafl::prelude::Error;
+use regex::bytes::Regex;
+use std::io::Read;
+use std::path::Path;
+use crate::packets::{Packet, PacketBasedInput};
+
+/// A closure for [`SplitRule::Closure`].
+pub type SplitFn = Box<dyn Fn(&[u8]) -> Option<usize>>;
+
+/// How a raw byte stream, like the transcript of a session, is cut into packets.
+pub enum SplitRule {
+    /// Every packet ends with `\r\n`.
+    Crlf,
+    
+    /// Every packet ends with `\n`.
+    Lf,
+    
+    /// Every packet ends with a match of the regex.
+    Delimiter(Regex),
+    
+    /// Every packet starts with its length, excluding the length field itself.
+    /// The length field is `width` bytes wide and stays part of the packet.
+    /// Create it with [`SplitRule::length_prefix`], which checks the width.
+    LengthPrefix {
+        width: usize,
+        big_endian: bool,
+    },
+    
+    /// The closure gets the rest of the stream and returns the length of the next packet.
+    /// `None` makes the rest of the stream the last packet.
+    Closure(SplitFn),
+}
+
+impl SplitRule {
+    /// Creates a [`SplitRule::Delimiter`] from a regex pattern.
+    pub fn delimiter(pattern: &str) -> Result<Self, Error> {
+        Regex::new(pattern)
+            .map(SplitRule::Delimiter)
+            .map_err(|e| Error::illegal_argument(format!("Invalid delimiter {pattern:?}: {e}")))
+    }
+    
+    /// Creates a [`SplitRule::LengthPrefix`] with a length field of 1 to 8 bytes.
+    pub fn length_prefix(width: usize, big_endian: bool) -> Result<Self, Error> {
+        if !(1..=8).contains(&width) {
+            return Err(Error::illegal_argument(format!("Invalid length prefix width {width}, must be between 1 and 8")));
+        }
+        
+        Ok(SplitRule::LengthPrefix {
+            width,
+            big_endian,
+        })
+    }
+    
+    fn is_valid(&self) -> bool {
+        match self {
+            SplitRule::LengthPrefix { width, .. } => (1..=8).contains(width),
+            _ => true,
+        }
+    }
+    
+    /// The length of the next packet at the start of `rest`.
+    fn next_len(&self, rest: &[u8]) -> Option<usize> {
+        match self {
+            SplitRule::Crlf => rest.windows(2).position(|w| w == b"\r\n").map(|pos| pos + 2),
+            SplitRule::Lf => rest.iter().position(|c| *c == b'\n').map(|pos| pos + 1),
+            SplitRule::Delimiter(regex) => regex.find(rest).filter(|m| m.end() > 0).map(|m| m.end()),
+            SplitRule::LengthPrefix { width, big_endian } => {
+                if rest.len() < *width {
+                    return None;
+                }
+                
+                let mut field = rest[..*width].to_vec();
+                
+                if !*big_endian {
+                    field.reverse();
+                }
+                
+                let len = field.iter().fold(0u64, |len, byte| (len << 8) | *byte as u64);
+                Some(width.saturating_add(usize::try_from(len).unwrap_or(usize::MAX)))
+            },
+            SplitRule::Closure(func) => func(rest),
+        }
+    }
+}
+
+impl<P> PacketBasedInput<P>
+where
+    P: Packet,
+{
+    /// Cuts a raw byte stream into packets according to `rule`.
+    /// Returns `None` if one of the packets cannot be parsed or the rule is invalid.
+    pub fn parse_stream(buf: &[u8], rule: &SplitRule) -> Option<Self> {
+        if !rule.is_valid() {
+            return None;
+        }
+        
+        let mut packets = vec![];
+        let mut cursor = 0;
+        
+        while cursor < buf.len() {
+            let rest = &buf[cursor..];
+            let len = match rule.next_len(rest) {
+                Some(len) if len > 0 => std::cmp::min(len, rest.len()),
+                _ => rest.len(),
+            };
+            
+            packets.push(P::deserialize_content(&rest[..len])?);
+            cursor += len;
+        }
+        
+        Some(Self::new(packets))
+    }
+    
+    /// Reads a raw transcript from a file and cuts it into packets according to `rule`.
+    pub fn read_stream<Q: AsRef<Path>>(path: Q, rule: &SplitRule) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let mut file = std::fs::File::open(path)?;
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes)?;
+        
+        Self::parse_stream(&bytes, rule).ok_or_else(|| Error::serialize(format!("Could not split {} into packets", path.display())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokens::TokenStream;
+    use libafl::prelude::BytesInput;
+    
+    fn contents<P: Packet>(input: &PacketBasedInput<P>) -> Vec<Vec<u8>> {
+        input.packets().iter().map(|p| p.content_to_vec()).collect()
+    }
+    
+    #[test]
+    fn test_split_rules() {
+        let transcript = b"USER a\r\nPASS b\r\nLIST\r\n";
+        let input = PacketBasedInput::<TokenStream>::parse_stream(transcript, &SplitRule::Crlf).unwrap();
+        assert_eq!(contents(&input), [b"USER a\r\n".to_vec(), b"PASS b\r\n".to_vec(), b"LIST\r\n".to_vec()]);
+        
+        let input = PacketBasedInput::<BytesInput>::parse_stream(b"a\nb\nc", &SplitRule::Lf).unwrap();
+        assert_eq!(contents(&input), [b"a\n".to_vec(), b"b\n".to_vec(), b"c".to_vec()]);
+        
+        let input = PacketBasedInput::<TokenStream>::parse_stream(b"EHLO x;;MAIL FROM:<a>;QUIT", &SplitRule::delimiter(";+").unwrap()).unwrap();
+        assert_eq!(contents(&input), [b"EHLO x;;".to_vec(), b"MAIL FROM:<a>;".to_vec(), b"QUIT".to_vec()]);
+        
+        let rule = SplitRule::length_prefix(2, true).unwrap();
+        let input = PacketBasedInput::<BytesInput>::parse_stream(b"\x00\x03abc\x00\x01d\x00\x09ef", &rule).unwrap();
+        assert_eq!(contents(&input), [b"\x00\x03abc".to_vec(), b"\x00\x01d".to_vec(), b"\x00\x09ef".to_vec()]);
+        
+        let rule = SplitRule::Closure(Box::new(|rest| rest.iter().position(|c| *c == b'.').map(|pos| pos + 1)));
+        let input = PacketBasedInput::<BytesInput>::parse_stream(b"one.two.", &rule).unwrap();
+        assert_eq!(contents(&input), [b"one.".to_vec(), b"two.".to_vec()]);
+        
+        let input = PacketBasedInput::<TokenStream>::parse_stream(b"USER \xff\r\n", &SplitRule::Crlf).unwrap();
+        assert_eq!(contents(&input), [b"USER \xff\r\n".to_vec()]);
+    }
+    
+    #[test]
+    fn test_length_prefix_width() {
+        assert!(SplitRule::length_prefix(0, true).is_err());
+        assert!(SplitRule::length_prefix(9, false).is_err());
+        
+        let rule = SplitRule::length_prefix(1, false).unwrap();
+        let input = PacketBasedInput::<BytesInput>::parse_stream(b"\x01a\x02bc", &rule).unwrap();
+        assert_eq!(contents(&input), [b"\x01a".to_vec(), b"\x02bc".to_vec()]);
+        
+        /* A rule built without the constructor does not swallow the stream */
+        let rule = SplitRule::LengthPrefix {
+            width: 0,
+            big_endian: true,
+        };
+        assert!(PacketBasedInput::<BytesInput>::parse_stream(b"\x01a", &rule).is_none());
+    }
+}
@@ -0,0 +1,244 @@
+use libafl::prelude::{MutationResult, Error};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::time::Duration;
+use crate::format::InputFormat;
+use crate::packets::{Packet, PacketMeta, PacketMutator, SplitPacket, SplitBoundary, RandomPacketCreator, MergePacket, ShrinkPacket, ShiftPacket};
+
+const DELAY_HEADER_LEN: usize = 8;
+
+/// A packet together with the time to wait before it is sent.
+///
+/// In frames files the delay is stored in front of the content as a 64-bit little-endian
+/// number of microseconds. txt files only hold the content that libdesock sends, `to_file` and `from_file`
+/// keep the delays in a hidden text file next to them, see [`crate::format::sidecar_path`].
+#[derive(Clone, Debug, Serialize, Deserialize, Hash)]
+pub struct Timed<P> {
+    delay: Duration,
+    packet: P,
+}
+
+impl<P> Timed<P> {
+    pub fn new(delay: Duration, packet: P) -> Self {
+        Self {
+            delay,
+            packet,
+        }
+    }
+    
+    pub fn packet(&self) -> &P {
+        &self.packet
+    }
+    
+    pub fn packet_mut(&mut self) -> &mut P {
+        &mut self.packet
+    }
+    
+    fn header(&self) -> [u8; DELAY_HEADER_LEN] {
+        u64::try_from(self.delay.as_micros()).unwrap_or(u64::MAX).to_le_bytes()
+    }
+}
+
+impl<P> Packet for Timed<P>
+where
+    P: Packet,
+{
+    fn serialize_content(&self, buffer: &mut [u8]) -> usize {
+        self.packet.serialize_content(buffer)
+    }
+    
+    fn deserialize_content(buffer: &[u8]) -> Option<Self> {
+        Some(Self::new(Duration::ZERO, P::deserialize_content(buffer)?))
+    }
+    
+    fn serialized_len(&self) -> usize {
+        self.packet.serialized_len()
+    }
+    
+    fn write_content<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.packet.write_content(writer)
+    }
+}
+
+impl<P> PacketMeta for Timed<P>
+where
+    P: PacketMeta,
+{
+    fn is_client(&self) -> bool {
+        self.packet.is_client()
+    }
+    
+    fn delay(&self) -> Duration {
+        self.delay
+    }
+    
+    fn set_delay(&mut self, delay: Duration) {
+        self.delay = delay;
+    }
+    
+    fn connection(&self) -> usize {
+        self.packet.connection()
+    }
+    
+    fn record_len(&self) -> usize {
+        DELAY_HEADER_LEN + self.packet.record_len()
+    }
+    
+    fn write_record<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(&self.header())?;
+        self.packet.write_record(writer)
+    }
+    
+    fn parse_record(buffer: &[u8]) -> Option<Self> {
+        let header = buffer.get(..DELAY_HEADER_LEN)?;
+        let micros = u64::from_le_bytes(header.try_into().ok()?);
+        let packet = P::parse_record(&buffer[DELAY_HEADER_LEN..])?;
+        Some(Self::new(Duration::from_micros(micros), packet))
+    }
+    
+    fn roundtrips(format: InputFormat) -> bool {
+        P::roundtrips(format)
+    }
+}
+
+impl<P, S, M> PacketMutator<Timed<P>, S> for M
+where
+    M: PacketMutator<P, S>,
+    P: Packet,
+{
+    fn mutate_packet(&mut self, state: &mut S, packet: &mut Timed<P>) -> Result<MutationResult, Error> {
+        self.mutate_packet(state, &mut packet.packet)
+    }
+}
+
+/// The part that is split off is sent right after the original packet.
+impl<P, S> SplitPacket<S> for Timed<P>
+where
+    P: SplitPacket<S>,
+{
+    fn split_packet(&mut self, state: &mut S) -> Option<Self> {
+        let other = self.packet.split_packet(state)?;
+        Some(Self::new(Duration::ZERO, other))
+    }
+    
+    fn split_packet_at(&mut self, state: &mut S, boundary: SplitBoundary) -> Option<Self> {
+        let other = self.packet.split_packet_at(state, boundary)?;
+        Some(Self::new(Duration::ZERO, other))
+    }
+}
+
+impl<P, S> RandomPacketCreator<S> for Timed<P>
+where
+    P: RandomPacketCreator<S>,
+{
+    fn create_random_packet(state: &mut S) -> Self {
+        Self::new(Duration::ZERO, P::create_random_packet(state))
+    }
+}
+
+/// Both packets keep their delays.
+impl<P> ShiftPacket for Timed<P>
+where
+    P: ShiftPacket,
+{
+    fn shift_units(&self) -> usize {
+        self.packet.shift_units()
+    }
+    
+    fn shift_boundary(&mut self, next: &mut Self, at: usize) -> bool {
+        self.packet.shift_boundary(&mut next.packet, at)
+    }
+}
+
+/// The merged packet is sent with the delay of the first packet.
+impl<P> MergePacket for Timed<P>
+where
+    P: MergePacket,
+{
+    fn merge_packet(&mut self, other: &Self) -> bool {
+        self.packet.merge_packet(&other.packet)
+    }
+}
+
+impl<P> ShrinkPacket for Timed<P>
+where
+    P: ShrinkPacket + Clone,
+{
+    fn shrink_packet<F>(&mut self, keep: &mut F) -> Result<(), Error>
+    where
+        F: FnMut(&Self) -> Result<bool, Error>,
+    {
+        let delay = self.delay;
+        self.packet.shrink_packet(&mut |packet: &P| keep(&Self::new(delay, packet.clone())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::PacketBasedInput;
+    use crate::tokens::TokenStream;
+    use libafl::prelude::Input;
+    
+    #[test]
+    fn test_records() {
+        let input = PacketBasedInput::new(vec![
+            Timed::new(Duration::ZERO, "USER a\r\n".parse::<TokenStream>().unwrap()),
+            Timed::new(Duration::from_micros(1500), "PASS b\r\n".parse::<TokenStream>().unwrap()),
+        ]);
+        
+        /* libdesock gets nothing but the content */
+        let txt = input.txt_to_vec(usize::MAX).unwrap();
+        assert_eq!(txt, b"USER a\r\n--------PASS b\r\n");
+        
+        let output = PacketBasedInput::<Timed<TokenStream>>::parse_txt(&txt).unwrap();
+        assert_eq!(output.packets()[1].delay(), Duration::ZERO);
+        assert_eq!(output.packets()[1].content_to_vec(), b"PASS b\r\n");
+        
+        let frames = input.frames_to_vec(usize::MAX).unwrap();
+        let output = PacketBasedInput::<Timed<TokenStream>>::parse_frames(&frames).unwrap();
+        assert_eq!(output.packets()[1].delay(), Duration::from_micros(1500));
+        assert_eq!(output.packets()[1].content_to_vec(), b"PASS b\r\n");
+        
+        /* Content that looks like metadata stays content */
+        let input = PacketBasedInput::new(vec![Timed::new(Duration::ZERO, "#delay_us=5\n".parse::<TokenStream>().unwrap())]);
+        let output = PacketBasedInput::<Timed<TokenStream>>::parse_txt(&input.txt_to_vec(usize::MAX).unwrap()).unwrap();
+        assert_eq!(output.packets()[0].delay(), Duration::ZERO);
+        assert_eq!(output.packets()[0].content_to_vec(), b"#delay_us=5\n");
+    }
+    
+    #[test]
+    fn test_txt_sidecar() {
+        let dir = std::env::temp_dir().join(format!("butterfly-timed-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("input.txt");
+        let sidecar = crate::format::sidecar_path(&path);
+        
+        let input = PacketBasedInput::new(vec![
+            Timed::new(Duration::ZERO, "USER a\r\n".parse::<TokenStream>().unwrap()),
+            Timed::new(Duration::from_micros(1500), "PASS b\r\n".parse::<TokenStream>().unwrap()),
+        ]);
+        input.to_file(&path).unwrap();
+        
+        /* The txt file stays content-only, the delays go into a hidden file */
+        assert_eq!(std::fs::read(&path).unwrap(), b"USER a\r\n--------PASS b\r\n");
+        assert_eq!(std::fs::read(&sidecar).unwrap(), b"0\n1500\n");
+        assert!(sidecar.file_name().unwrap().to_string_lossy().starts_with('.'));
+        
+        let output = PacketBasedInput::<Timed<TokenStream>>::from_file(&path).unwrap();
+        assert_eq!(output.packets()[0].delay(), Duration::ZERO);
+        assert_eq!(output.packets()[1].delay(), Duration::from_micros(1500));
+        assert_eq!(output.packets()[1].content_to_vec(), b"PASS b\r\n");
+        
+        /* Overwriting with an input without delays drops the sidecar */
+        let input = PacketBasedInput::new(vec![Timed::new(Duration::ZERO, "QUIT\r\n".parse::<TokenStream>().unwrap())]);
+        input.to_file(&path).unwrap();
+        assert!(!sidecar.exists());
+        
+        /* A sidecar that doesn't match the packets is an error */
+        std::fs::write(&sidecar, b"1\n2\n").unwrap();
+        assert!(PacketBasedInput::<Timed<TokenStream>>::from_file(&path).is_err());
+        
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
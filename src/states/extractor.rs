@@ -0,0 +1,112 @@
+/// The id of a protocol state.
+pub type StateId = u32;
+
+/// The state that a session is in before the target has sent anything.
+/// Extractors should not produce this id.
+pub const INITIAL_STATE: StateId = 0;
+
+/// Infers the protocol state of the target from a response.
+pub trait StateExtractor {
+    /// Returns the state that the target is in after sending `response`
+    /// or `None` if the response does not tell.
+    fn extract_state(&mut self, response: &[u8]) -> Option<StateId>;
+}
+
+/// Uses the numeric reply codes of text protocols as state ids.
+///
+/// Works for protocols where replies start with a three-digit code, like FTP and SMTP,
+/// and for protocols with a status line, like RTSP, HTTP and SIP.
+/// If a response contains multiple replies, the last one determines the state.
+pub struct NumericReplyCode;
+
+impl NumericReplyCode {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self
+    }
+    
+    fn parse_code(line: &[u8]) -> Option<StateId> {
+        if line.len() < 3 || !line[..3].iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        
+        if line.len() > 3 && !matches!(line[3], b' ' | b'-' | b'\r') {
+            return None;
+        }
+        
+        Some(line[..3].iter().fold(0, |code, digit| code * 10 + (*digit - b'0') as StateId))
+    }
+    
+    /* Status lines look like "RTSP/1.0 200 OK" */
+    fn parse_status_line(line: &[u8]) -> Option<StateId> {
+        let slash = line.iter().position(|c| *c == b'/')?;
+        
+        if slash == 0 || !line[..slash].iter().all(u8::is_ascii_uppercase) {
+            return None;
+        }
+        
+        let space = line.iter().position(|c| *c == b' ')?;
+        Self::parse_code(&line[space + 1..])
+    }
+}
+
+impl StateExtractor for NumericReplyCode {
+    fn extract_state(&mut self, response: &[u8]) -> Option<StateId> {
+        let mut lines = response.split(|c| *c == b'\n');
+        
+        if let Some(code) = Self::parse_status_line(lines.next()?) {
+            return Some(code);
+        }
+        
+        response.split(|c| *c == b'\n').rev().find_map(Self::parse_code)
+    }
+}
+
+/// Lets a closure decide the state.
+pub struct ClosureStateExtractor<F>
+where
+    F: FnMut(&[u8]) -> Option<StateId>,
+{
+    func: F,
+}
+
+impl<F> ClosureStateExtractor<F>
+where
+    F: FnMut(&[u8]) -> Option<StateId>,
+{
+    pub fn new(func: F) -> Self {
+        Self {
+            func,
+        }
+    }
+}
+
+impl<F> StateExtractor for ClosureStateExtractor<F>
+where
+    F: FnMut(&[u8]) -> Option<StateId>,
+{
+    fn extract_state(&mut self, response: &[u8]) -> Option<StateId> {
+        (self.func)(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_reply_codes() {
+        let mut extractor = NumericReplyCode::new();
+        assert_eq!(extractor.extract_state(b"220 ProFTPD Server ready\r\n"), Some(220));
+        assert_eq!(extractor.extract_state(b"230-Welcome\r\n230-to the server\r\n230 Login successful\r\n"), Some(230));
+        assert_eq!(extractor.extract_state(b"250-mail.example.com\r\n250-STARTTLS\r\n250 8BITMIME\r\n"), Some(250));
+        assert_eq!(extractor.extract_state(b"RTSP/1.0 454 Session Not Found\r\nCSeq: 3\r\n\r\n"), Some(454));
+        assert_eq!(extractor.extract_state(b"HTTP/1.1 200 OK\r\n\r\n404 \r\n"), Some(200));
+        assert_eq!(extractor.extract_state(b"hello\r\n"), None);
+        assert_eq!(extractor.extract_state(b"2200\r\n"), None);
+        assert_eq!(extractor.extract_state(b""), None);
+        
+        let mut extractor = ClosureStateExtractor::new(|response| response.first().map(|c| *c as StateId));
+        assert_eq!(extractor.extract_state(b"+OK"), Some(b'+' as StateId));
+    }
+}
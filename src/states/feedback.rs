@@ -0,0 +1,143 @@
+use libafl::prelude::{Feedback, StateInitializer, HasMetadata, ExitKind, Testcase, Error};
+use libafl_bolts::prelude::{Named, Handle, Handled, MatchName, MatchNameRef};
+use std::borrow::Cow;
+use crate::net::ResponseObserver;
+use crate::states::{StateExtractor, StateGraph, StateTrace};
+
+/// Marks an input as interesting if it makes the target go through a protocol state
+/// or a state transition that has not been seen before, like AFLNet does.
+///
+/// The states are extracted from the responses in a [`ResponseObserver`].
+/// The [`StateGraph`] of the target is kept in the state metadata and every new testcase
+/// gets its [`StateTrace`] as metadata.
+pub struct StateFeedback<E>
+where
+    E: StateExtractor,
+{
+    observer: Handle<ResponseObserver>,
+    extractor: E,
+    last_trace: Option<StateTrace>,
+}
+
+impl<E> StateFeedback<E>
+where
+    E: StateExtractor,
+{
+    pub fn new(observer: &ResponseObserver, extractor: E) -> Self {
+        Self {
+            observer: observer.handle(),
+            extractor,
+            last_trace: None,
+        }
+    }
+    
+    fn trace(&mut self, observer: &ResponseObserver) -> StateTrace {
+        let mut trace = StateTrace::new();
+        
+        for (idx, response) in observer.responses().iter().enumerate() {
+            if response.is_empty() {
+                continue;
+            }
+            
+            if let Some(state) = self.extractor.extract_state(response) {
+                trace.push(idx, state);
+            }
+        }
+        
+        trace
+    }
+}
+
+impl<E> Named for StateFeedback<E>
+where
+    E: StateExtractor,
+{
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("StateFeedback");
+        &NAME
+    }
+}
+
+impl<E, S> StateInitializer<S> for StateFeedback<E>
+where
+    E: StateExtractor,
+    S: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        if !state.has_metadata::<StateGraph>() {
+            state.add_metadata(StateGraph::new());
+        }
+        Ok(())
+    }
+}
+
+impl<E, EM, I, OT, S> Feedback<EM, I, OT, S> for StateFeedback<E>
+where
+    E: StateExtractor,
+    OT: MatchName,
+    S: HasMetadata,
+{
+    fn is_interesting(&mut self, state: &mut S, _manager: &mut EM, _input: &I, observers: &OT, _exit_kind: &ExitKind) -> Result<bool, Error> {
+        let observer = observers.get(&self.observer).ok_or_else(|| Error::key_not_found(format!("ResponseObserver {} not found", self.observer.name())))?;
+        let trace = self.trace(observer);
+        let graph = state.metadata_or_insert_with(StateGraph::new);
+        let interesting = graph.is_novel(&trace);
+        graph.add_trace(&trace);
+        self.last_trace = Some(trace);
+        Ok(interesting)
+    }
+    
+    fn append_metadata(&mut self, _state: &mut S, _manager: &mut EM, _observers: &OT, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        if let Some(trace) = self.last_trace.take() {
+            testcase.add_metadata(trace);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::states::NumericReplyCode;
+    use libafl::prelude::{NopState, BytesInput};
+    use libafl_bolts::prelude::tuple_list;
+    
+    fn observe(observer: &mut ResponseObserver, responses: &[&[u8]]) {
+        observer.clear();
+        
+        for (idx, response) in responses.iter().enumerate() {
+            observer.record(idx, response.to_vec());
+        }
+    }
+    
+    #[test]
+    fn test_new_states() {
+        let mut state = NopState::<BytesInput>::new();
+        let mut observer = ResponseObserver::new("responses", 4096);
+        let mut feedback = StateFeedback::new(&observer, NumericReplyCode::new());
+        let input = BytesInput::from(vec![]);
+        feedback.init_state(&mut state).unwrap();
+        
+        let mut run = |observer: &ResponseObserver, state: &mut NopState<BytesInput>| {
+            let observers = tuple_list!(observer.clone());
+            feedback.is_interesting(state, &mut (), &input, &observers, &ExitKind::Ok).unwrap()
+        };
+        
+        observe(&mut observer, &[b"331 password\r\n", b"530 denied\r\n"]);
+        assert!(run(&observer, &mut state));
+        assert!(!run(&observer, &mut state));
+        
+        /* Same states, new transition */
+        observe(&mut observer, &[b"530 denied\r\n", b"331 password\r\n"]);
+        assert!(run(&observer, &mut state));
+        
+        /* New state */
+        observe(&mut observer, &[b"331 password\r\n", b"", b"230 ok\r\n"]);
+        assert!(run(&observer, &mut state));
+        
+        let graph = state.metadata::<StateGraph>().unwrap();
+        assert_eq!(graph.num_states(), 3);
+        assert_eq!(graph.state(331).unwrap().visits, 4);
+        assert_eq!(graph.transition_count(331, 530), 2);
+    }
+}
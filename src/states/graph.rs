@@ -0,0 +1,126 @@
+use libafl_bolts::impl_serdeany;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::states::{StateId, INITIAL_STATE};
+
+/// The protocol states that an input went through, in order.
+/// Every entry holds the index of the packet whose response revealed the state.
+/// Stored as testcase metadata by the [`crate::states::StateFeedback`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateTrace {
+    states: Vec<(usize, StateId)>,
+}
+
+impl_serdeany!(StateTrace);
+
+impl StateTrace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn push(&mut self, packet: usize, state: StateId) {
+        self.states.push((packet, state));
+    }
+    
+    /// Pairs of packet index and state.
+    pub fn states(&self) -> &[(usize, StateId)] {
+        &self.states
+    }
+    
+    /// The index of the packet after which `state` was reached for the first time.
+    pub fn first_reached(&self, state: StateId) -> Option<usize> {
+        self.states.iter().find(|(_, s)| *s == state).map(|(packet, _)| *packet)
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+    
+    /// All transitions of the trace, starting at [`INITIAL_STATE`].
+    pub fn transitions(&self) -> impl Iterator<Item = (StateId, StateId)> + '_ {
+        std::iter::once(INITIAL_STATE)
+            .chain(self.states.iter().map(|(_, state)| *state))
+            .zip(self.states.iter().map(|(_, state)| *state))
+    }
+}
+
+/// Statistics about a single protocol state.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StateInfo {
+    /// How many executions reached the state.
+    pub visits: u64,
+    
+    /// How often the [`crate::states::StateScheduler`] picked the state as target.
+    pub selected: u64,
+    
+    /// How many new testcases were found while the state was the target.
+    pub found: u64,
+}
+
+/// The state machine of the target as far as it has been observed.
+/// Stored as state metadata by the [`crate::states::StateFeedback`].
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StateGraph {
+    states: HashMap<StateId, StateInfo>,
+    transitions: HashMap<(StateId, StateId), u64>,
+}
+
+impl_serdeany!(StateGraph);
+
+impl StateGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn states(&self) -> impl Iterator<Item = (StateId, &StateInfo)> {
+        self.states.iter().map(|(id, info)| (*id, info))
+    }
+    
+    pub fn state(&self, state: StateId) -> Option<&StateInfo> {
+        self.states.get(&state)
+    }
+    
+    pub fn state_mut(&mut self, state: StateId) -> Option<&mut StateInfo> {
+        self.states.get_mut(&state)
+    }
+    
+    pub fn num_states(&self) -> usize {
+        self.states.len()
+    }
+    
+    pub fn num_transitions(&self) -> usize {
+        self.transitions.len()
+    }
+    
+    pub fn has_transition(&self, from: StateId, to: StateId) -> bool {
+        self.transitions.contains_key(&(from, to))
+    }
+    
+    /// How often the transition was taken.
+    pub fn transition_count(&self, from: StateId, to: StateId) -> u64 {
+        self.transitions.get(&(from, to)).copied().unwrap_or(0)
+    }
+    
+    /// Whether the trace contains a state or a transition that is not part of the graph.
+    pub fn is_novel(&self, trace: &StateTrace) -> bool {
+        trace.states().iter().any(|(_, state)| !self.states.contains_key(state))
+            || trace.transitions().any(|(from, to)| !self.has_transition(from, to))
+    }
+    
+    /// Adds the states and transitions of an execution to the graph.
+    /// Every state is counted once per trace.
+    pub fn add_trace(&mut self, trace: &StateTrace) {
+        let mut seen = Vec::<StateId>::new();
+        
+        for (_, state) in trace.states() {
+            if !seen.contains(state) {
+                seen.push(*state);
+                self.states.entry(*state).or_default().visits += 1;
+            }
+        }
+        
+        for transition in trace.transitions() {
+            *self.transitions.entry(transition).or_insert(0) += 1;
+        }
+    }
+}
@@ -0,0 +1,9 @@
+mod extractor;
+mod graph;
+mod feedback;
+mod scheduler;
+
+pub use extractor::*;
+pub use graph::*;
+pub use feedback::*;
+pub use scheduler::*;
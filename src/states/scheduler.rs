@@ -0,0 +1,317 @@
+use libafl::prelude::{Scheduler, RemovableScheduler, HasCorpus, Corpus, CorpusId, HasMetadata, HasRand, Error, Testcase};
+use libafl_bolts::{impl_serdeany, prelude::Rand};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use crate::packets::{Packet, PacketBasedInput};
+use crate::states::{StateId, StateGraph, StateTrace};
+
+/// The index of the packet after which a testcase reached each of its states for the first time.
+/// Added to every testcase by the [`StateScheduler`].
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ReachedStates {
+    reached: BTreeMap<StateId, usize>,
+}
+
+impl_serdeany!(ReachedStates);
+
+impl ReachedStates {
+    pub fn from_trace(trace: &StateTrace) -> Self {
+        let mut reached = BTreeMap::new();
+        
+        for (packet, state) in trace.states() {
+            reached.entry(*state).or_insert(*packet);
+        }
+        
+        Self {
+            reached,
+        }
+    }
+    
+    pub fn packet(&self, state: StateId) -> Option<usize> {
+        self.reached.get(&state).copied()
+    }
+    
+    pub fn states(&self) -> impl Iterator<Item = (StateId, usize)> + '_ {
+        self.reached.iter().map(|(state, packet)| (*state, *packet))
+    }
+}
+
+/// The state that the [`StateScheduler`] was aiming for when it picked a testcase.
+/// The packets up to and including `packet` bring the target into `state`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ScheduledState {
+    pub state: StateId,
+    pub packet: usize,
+}
+
+impl_serdeany!(ScheduledState);
+
+/// The testcases that reach each state, kept in the state metadata.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct StateCorpus {
+    testcases: BTreeMap<StateId, Vec<CorpusId>>,
+}
+
+impl_serdeany!(StateCorpus);
+
+impl StateCorpus {
+    fn remove(&mut self, id: CorpusId) {
+        for testcases in self.testcases.values_mut() {
+            testcases.retain(|other| *other != id);
+        }
+        
+        self.testcases.retain(|_, testcases| !testcases.is_empty());
+    }
+}
+
+/// How the [`StateScheduler`] picks the state to fuzz next.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateSelection {
+    /// Prefer states that few executions reach.
+    Rarity,
+    
+    /// Prefer states that led to new testcases when they were picked before.
+    Productivity,
+}
+
+/// A scheduler that first picks a protocol state and then a testcase that reaches it,
+/// like AFLNet does.
+///
+/// Needs the [`StateTrace`]s that the [`crate::states::StateFeedback`] attaches to testcases.
+/// Testcases without a trace are only picked when no state is known yet.
+/// The picked testcase gets a [`ScheduledState`] as metadata.
+pub struct StateScheduler {
+    selection: StateSelection,
+    current: Option<StateId>,
+}
+
+impl StateScheduler {
+    pub fn new(selection: StateSelection) -> Self {
+        Self {
+            selection,
+            current: None,
+        }
+    }
+    
+    fn weight(&self, graph: &StateGraph, state: StateId) -> f64 {
+        let Some(info) = graph.state(state) else {
+            return 1.0;
+        };
+        
+        match self.selection {
+            StateSelection::Rarity => 1.0 / (info.visits as f64 + 1.0),
+            StateSelection::Productivity => (info.found as f64 + 1.0) / (info.selected as f64 + 1.0),
+        }
+    }
+    
+    fn pick_state<S>(&self, state: &mut S) -> Option<(StateId, CorpusId)>
+    where
+        S: HasMetadata + HasRand,
+    {
+        let graph = state.metadata::<StateGraph>().ok()?;
+        let candidates: Vec<(StateId, f64, usize)> = state.metadata::<StateCorpus>().ok()?.testcases
+            .iter()
+            .filter(|(_, testcases)| !testcases.is_empty())
+            .map(|(id, testcases)| (*id, self.weight(graph, *id), testcases.len()))
+            .collect();
+        let total: f64 = candidates.iter().map(|(_, weight, _)| *weight).sum();
+        let mut choice = state.rand_mut().next_float() * total;
+        let (mut target, _, mut count) = *candidates.last()?;
+        
+        for (id, weight, len) in &candidates {
+            if choice < *weight {
+                target = *id;
+                count = *len;
+                break;
+            }
+            choice -= *weight;
+        }
+        
+        let idx = state.rand_mut().below_or_zero(count);
+        let testcases = &state.metadata::<StateCorpus>().ok()?.testcases[&target];
+        Some((target, testcases[idx]))
+    }
+    
+    /// Derives the [`ReachedStates`] of a testcase from its trace and files it under every reached state.
+    fn index_testcase<I, S>(&self, state: &mut S, id: CorpusId) -> Result<(), Error>
+    where
+        S: HasCorpus<I> + HasMetadata,
+    {
+        let reached = {
+            let mut testcase = state.corpus().get(id)?.borrow_mut();
+            let reached = testcase.metadata::<StateTrace>().ok().map(ReachedStates::from_trace);
+            
+            match &reached {
+                Some(reached) => testcase.add_metadata(reached.clone()),
+                None => drop(testcase.metadata_map_mut().remove::<ReachedStates>()),
+            }
+            
+            reached
+        };
+        
+        if let Some(reached) = reached {
+            let corpus = state.metadata_or_insert_with(StateCorpus::default);
+            
+            for (target, _) in reached.states() {
+                corpus.testcases.entry(target).or_default().push(id);
+            }
+        }
+        
+        Ok(())
+    }
+}
+
+impl<P, S> RemovableScheduler<PacketBasedInput<P>, S> for StateScheduler
+where
+    P: Packet,
+    S: HasCorpus<PacketBasedInput<P>> + HasMetadata,
+{
+    fn on_remove(&mut self, state: &mut S, id: CorpusId, _testcase: &Option<Testcase<PacketBasedInput<P>>>) -> Result<(), Error> {
+        if let Ok(corpus) = state.metadata_mut::<StateCorpus>() {
+            corpus.remove(id);
+        }
+        
+        Ok(())
+    }
+    
+    /// The new testcase can reach other states, or the same states after other packets.
+    fn on_replace(&mut self, state: &mut S, id: CorpusId, _prev: &Testcase<PacketBasedInput<P>>) -> Result<(), Error> {
+        if let Ok(corpus) = state.metadata_mut::<StateCorpus>() {
+            corpus.remove(id);
+        }
+        
+        self.index_testcase(state, id)
+    }
+}
+
+impl<P, S> Scheduler<PacketBasedInput<P>, S> for StateScheduler
+where
+    P: Packet,
+    S: HasCorpus<PacketBasedInput<P>> + HasMetadata + HasRand,
+{
+    fn on_add(&mut self, state: &mut S, id: CorpusId) -> Result<(), Error> {
+        let current_id = *state.corpus().current();
+        state.corpus().get(id)?.borrow_mut().set_parent_id_optional(current_id);
+        
+        if let Some(target) = self.current
+            && let Some(info) = state.metadata_or_insert_with(StateGraph::new).state_mut(target)
+        {
+            info.found += 1;
+        }
+        
+        self.index_testcase(state, id)
+    }
+    
+    fn next(&mut self, state: &mut S) -> Result<CorpusId, Error> {
+        if state.corpus().count() == 0 {
+            return Err(Error::empty("No entries in corpus".to_owned()));
+        }
+        
+        let id = match self.pick_state(state) {
+            Some((target, id)) => {
+                if let Some(info) = state.metadata_or_insert_with(StateGraph::new).state_mut(target) {
+                    info.selected += 1;
+                }
+                
+                let mut testcase = state.corpus().get(id)?.borrow_mut();
+                let packet = testcase.metadata::<ReachedStates>()?.packet(target).unwrap_or(0);
+                testcase.add_metadata(ScheduledState {
+                    state: target,
+                    packet,
+                });
+                
+                self.current = Some(target);
+                id
+            },
+            None => {
+                self.current = None;
+                let count = state.corpus().count();
+                let nth = state.rand_mut().below_or_zero(count);
+                state.corpus().nth(nth)
+            },
+        };
+        
+        <Self as Scheduler<PacketBasedInput<P>, S>>::set_current_scheduled(self, state, Some(id))?;
+        Ok(id)
+    }
+    
+    fn set_current_scheduled(&mut self, state: &mut S, next_id: Option<CorpusId>) -> Result<(), Error> {
+        *state.corpus_mut().current_mut() = next_id;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libafl::prelude::{StdState, InMemoryCorpus, Testcase, BytesInput};
+    use libafl_bolts::prelude::StdRand;
+    
+    fn testcase(trace: &[(usize, StateId)]) -> Testcase<PacketBasedInput<BytesInput>> {
+        let mut testcase = Testcase::new(PacketBasedInput::new(vec![BytesInput::from(&b"NOOP\r\n"[..]); 4]));
+        let mut metadata = StateTrace::new();
+        
+        for (packet, id) in trace {
+            metadata.push(*packet, *id);
+        }
+        
+        testcase.add_metadata(metadata);
+        testcase
+    }
+    
+    #[test]
+    fn test_rare_states() {
+        let mut state = StdState::new(StdRand::with_seed(0), InMemoryCorpus::new(), InMemoryCorpus::new(), &mut (), &mut ()).unwrap();
+        let mut scheduler = StateScheduler::new(StateSelection::Rarity);
+        
+        let common = state.corpus_mut().add(testcase(&[(0, 220), (1, 331)])).unwrap();
+        scheduler.on_add(&mut state, common).unwrap();
+        let rare = state.corpus_mut().add(testcase(&[(0, 220), (2, 230)])).unwrap();
+        scheduler.on_add(&mut state, rare).unwrap();
+        
+        let mut graph = StateGraph::new();
+        
+        for _ in 0..1000 {
+            graph.add_trace(state.corpus().get(common).unwrap().borrow().metadata::<StateTrace>().unwrap());
+        }
+        
+        graph.add_trace(state.corpus().get(rare).unwrap().borrow().metadata::<StateTrace>().unwrap());
+        state.add_metadata(graph);
+        
+        let mut rare_picks = 0;
+        
+        for _ in 0..100 {
+            let id = scheduler.next(&mut state).unwrap();
+            let testcase = state.corpus().get(id).unwrap().borrow();
+            let scheduled = testcase.metadata::<ScheduledState>().unwrap();
+            assert_eq!(Some(scheduled.packet), testcase.metadata::<ReachedStates>().unwrap().packet(scheduled.state));
+            
+            if scheduled.state == 230 {
+                assert_eq!(id, rare);
+                assert_eq!(scheduled.packet, 2);
+                rare_picks += 1;
+            }
+        }
+        
+        assert!(rare_picks > 50);
+    }
+    
+    #[test]
+    fn test_replace_and_remove() {
+        let mut state = StdState::new(StdRand::with_seed(0), InMemoryCorpus::new(), InMemoryCorpus::new(), &mut (), &mut ()).unwrap();
+        let mut scheduler = StateScheduler::new(StateSelection::Rarity);
+        
+        let id = state.corpus_mut().add(testcase(&[(0, 220), (3, 230)])).unwrap();
+        scheduler.on_add(&mut state, id).unwrap();
+        
+        /* The replacement reaches 230 after fewer packets */
+        let prev = state.corpus_mut().replace(id, testcase(&[(0, 220), (1, 230)])).unwrap();
+        scheduler.on_replace(&mut state, id, &prev).unwrap();
+        assert_eq!(state.corpus().get(id).unwrap().borrow().metadata::<ReachedStates>().unwrap().packet(230), Some(1));
+        assert_eq!(state.metadata::<StateCorpus>().unwrap().testcases[&230], [id]);
+        
+        let prev = state.corpus_mut().remove(id).unwrap();
+        scheduler.on_remove(&mut state, id, &Some(prev)).unwrap();
+        assert!(state.metadata::<StateCorpus>().unwrap().testcases.is_empty());
+    }
+}
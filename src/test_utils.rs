@@ -0,0 +1,23 @@
+use libafl::prelude::HasRand;
+use libafl_bolts::prelude::{StdRand, current_nanos};
+
+/// The smallest state that packet mutators can run on.
+pub(crate) struct TestState(StdRand);
+
+impl TestState {
+    pub(crate) fn new() -> Self {
+        Self(StdRand::with_seed(current_nanos()))
+    }
+}
+
+impl HasRand for TestState {
+    type Rand = StdRand;
+    
+    fn rand(&self) -> &StdRand {
+        &self.0
+    }
+    
+    fn rand_mut(&mut self) -> &mut StdRand {
+        &mut self.0
+    }
+}
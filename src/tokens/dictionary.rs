@@ -0,0 +1,320 @@
+use libafl::prelude::{Input, Tokens, Stage, Restartable, HasCorpus, Corpus, CorpusId, HasMetadata, HasScheduler, RemovableScheduler, Error};
+use libafl_bolts::prelude::{Named, generic_hash_std};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::path::Path;
+use crate::packets::{Packet, PacketBasedInput, Directed, Timed, Connected};
+use crate::tokens::{TokenStream, TextToken, Tokenizer, DefaultTokenizer};
+
+/// A [`Tokenizer`] that turns occurrences of dictionary entries into [`TextToken::Constant`]s,
+/// so that keywords like `USER` or `Content-Length` are not corrupted by the mutators.
+///
+/// Entries that start or end with a letter or digit only match on word boundaries,
+/// the longest matching entry wins. Everything else is lexed by the inner tokenizer,
+/// the [`DefaultTokenizer`] unless one is given to [`DictionaryTokenizer::with_tokenizer`].
+/// Load the seeds with it via [`Retokenize::from_file_with`].
+pub struct DictionaryTokenizer<T = DefaultTokenizer> {
+    entries: HashMap<u8, Vec<Vec<u8>>>,
+    inner: T,
+}
+
+impl DictionaryTokenizer {
+    pub fn new(dict: &Tokens) -> Self {
+        Self::with_tokenizer(dict, DefaultTokenizer)
+    }
+}
+
+impl<T> DictionaryTokenizer<T>
+where
+    T: Tokenizer,
+{
+    pub fn with_tokenizer(dict: &Tokens, inner: T) -> Self {
+        let mut entries: HashMap<u8, Vec<Vec<u8>>> = HashMap::new();
+        
+        for entry in dict.tokens() {
+            if let Some(first) = entry.first() {
+                entries.entry(*first).or_default().push(entry.clone());
+            }
+        }
+        
+        for candidates in entries.values_mut() {
+            candidates.sort_by_key(|entry| std::cmp::Reverse(entry.len()));
+        }
+        
+        Self {
+            entries,
+            inner,
+        }
+    }
+    
+    /* Length of the longest entry that matches at `pos` */
+    fn match_at(&self, data: &[u8], pos: usize) -> Option<usize> {
+        let candidates = self.entries.get(&data[pos])?;
+        let word_start = pos == 0 || !data[pos - 1].is_ascii_alphanumeric();
+        
+        candidates.iter().find(|entry| {
+            let end = pos + entry.len();
+            data[pos..].starts_with(entry) &&
+                (word_start || !entry[0].is_ascii_alphanumeric()) &&
+                (end == data.len() || !data[end].is_ascii_alphanumeric() || !entry[entry.len() - 1].is_ascii_alphanumeric())
+        }).map(|entry| entry.len())
+    }
+}
+
+impl<T> Tokenizer for DictionaryTokenizer<T>
+where
+    T: Tokenizer,
+{
+    fn tokenize(&self, data: &[u8]) -> Vec<TextToken> {
+        let mut tokens = Vec::new();
+        let mut start = 0;
+        let mut cursor = 0;
+        
+        while cursor < data.len() {
+            if let Some(len) = self.match_at(data, cursor) {
+                tokens.extend(self.inner.tokenize(&data[start..cursor]));
+                tokens.push(TextToken::Constant(data[cursor..cursor + len].to_vec()));
+                cursor += len;
+                start = cursor;
+            } else {
+                cursor += 1;
+            }
+        }
+        
+        tokens.extend(self.inner.tokenize(&data[start..]));
+        tokens
+    }
+}
+
+/// Inputs whose content can be lexed again, for example after the dictionary has grown.
+pub trait Retokenize {
+    /// Lexes everything except the existing constants again with `tokenizer`.
+    /// Returns whether the tokens changed.
+    fn retokenize(&mut self, tokenizer: &dyn Tokenizer) -> bool;
+    
+    /// Loads an input from a file and lexes it again with `tokenizer`,
+    /// for tokenizers like the [`DictionaryTokenizer`] that cannot be the type parameter of a [`TokenStream`].
+    fn from_file_with<F>(tokenizer: &dyn Tokenizer, path: F) -> Result<Self, Error>
+    where
+        Self: Input,
+        F: AsRef<Path>,
+    {
+        let mut input = Self::from_file(path)?;
+        input.retokenize(tokenizer);
+        Ok(input)
+    }
+}
+
+impl<T> Retokenize for TokenStream<T> {
+    fn retokenize(&mut self, tokenizer: &dyn Tokenizer) -> bool {
+        let old_hash = generic_hash_std(self);
+        let mut tokens = Vec::with_capacity(self.tokens().len());
+        let mut pending = Vec::new();
+        
+        for token in self.tokens() {
+            if token.is_constant() {
+                tokens.extend(tokenizer.tokenize(&pending));
+                tokens.push(token.clone());
+                pending.clear();
+            } else {
+                pending.extend_from_slice(token.data());
+            }
+        }
+        
+        tokens.extend(tokenizer.tokenize(&pending));
+        *self = Self::new(tokens);
+        generic_hash_std(self) != old_hash
+    }
+}
+
+impl<P> Retokenize for PacketBasedInput<P>
+where
+    P: Packet + Retokenize,
+{
+    fn retokenize(&mut self, tokenizer: &dyn Tokenizer) -> bool {
+        let mut changed = false;
+        
+        for packet in self.packets_mut() {
+            changed |= packet.retokenize(tokenizer);
+        }
+        
+        changed
+    }
+}
+
+impl<P> Retokenize for Directed<P>
+where
+    P: Retokenize,
+{
+    fn retokenize(&mut self, tokenizer: &dyn Tokenizer) -> bool {
+        self.packet_mut().retokenize(tokenizer)
+    }
+}
+
+impl<P> Retokenize for Timed<P>
+where
+    P: Retokenize,
+{
+    fn retokenize(&mut self, tokenizer: &dyn Tokenizer) -> bool {
+        self.packet_mut().retokenize(tokenizer)
+    }
+}
+
+impl<P> Retokenize for Connected<P>
+where
+    P: Retokenize,
+{
+    fn retokenize(&mut self, tokenizer: &dyn Tokenizer) -> bool {
+        self.packet_mut().retokenize(tokenizer)
+    }
+}
+
+/// A stage that lexes all corpus entries again with a [`DictionaryTokenizer`]
+/// whenever the [`Tokens`] metadata of the state has grown, for example through
+/// a `TokensFromFile` dictionary or tokens that were learned during the campaign.
+pub struct RetokenizeStage<I, T = DefaultTokenizer> {
+    tokenizer: T,
+    dict_hash: Option<u64>,
+    phantom: PhantomData<I>,
+}
+
+impl<I> RetokenizeStage<I> {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self::with_tokenizer(DefaultTokenizer)
+    }
+}
+
+impl<I, T> RetokenizeStage<I, T> {
+    /// Lexes everything that is not a dictionary entry with `tokenizer`.
+    pub fn with_tokenizer(tokenizer: T) -> Self {
+        Self {
+            tokenizer,
+            dict_hash: None,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I, T> Named for RetokenizeStage<I, T> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("RetokenizeStage");
+        &NAME
+    }
+}
+
+impl<I, S, T> Restartable<S> for RetokenizeStage<I, T> {
+    fn should_restart(&mut self, _state: &mut S) -> Result<bool, Error> {
+        /* Retokenizing twice does no harm */
+        Ok(true)
+    }
+    
+    fn clear_progress(&mut self, _state: &mut S) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<E, EM, I, S, T, Z> Stage<E, EM, S, Z> for RetokenizeStage<I, T>
+where
+    I: Retokenize + Clone,
+    S: HasMetadata + HasCorpus<I>,
+    T: Tokenizer + Clone,
+    Z: HasScheduler<I, S>,
+    Z::Scheduler: RemovableScheduler<I, S>,
+{
+    fn perform(&mut self, fuzzer: &mut Z, _executor: &mut E, state: &mut S, _manager: &mut EM) -> Result<(), Error> {
+        let Some(dict) = state.metadata_map().get::<Tokens>() else {
+            return Ok(());
+        };
+        
+        /* Entries can also be replaced without changing the size of the dictionary */
+        let dict_hash = generic_hash_std(&dict.tokens());
+        
+        if self.dict_hash == Some(dict_hash) {
+            return Ok(());
+        }
+        
+        self.dict_hash = Some(dict_hash);
+        let tokenizer = DictionaryTokenizer::with_tokenizer(dict, self.tokenizer.clone());
+        let ids: Vec<CorpusId> = state.corpus().ids().collect();
+        
+        for id in ids {
+            let mut input = state.corpus().cloned_input_for_id(id)?;
+            
+            if input.retokenize(&tokenizer) {
+                let mut testcase = state.corpus().get(id)?.borrow().clone();
+                testcase.set_input(input);
+                let prev = state.corpus_mut().replace(id, testcase)?;
+                fuzzer.scheduler_mut().on_replace(state, id, &prev)?;
+            }
+        }
+        
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libafl::prelude::{StdState, StdFuzzer, InMemoryCorpus, QueueScheduler, Testcase};
+    use libafl_bolts::prelude::StdRand;
+    
+    /* Lexes everything as a single raw token */
+    #[derive(Clone)]
+    struct RawTokenizer;
+    
+    impl Tokenizer for RawTokenizer {
+        fn tokenize(&self, data: &[u8]) -> Vec<TextToken> {
+            if data.is_empty() {
+                Vec::new()
+            } else {
+                vec![TextToken::Raw(data.to_vec())]
+            }
+        }
+    }
+    
+    fn constants(stream: &TokenStream) -> Vec<&[u8]> {
+        stream.tokens().iter().filter(|t| t.is_constant()).map(|t| t.data()).collect()
+    }
+    
+    #[test]
+    fn test_dictionary() {
+        let mut dict = Tokens::new();
+        dict.add_tokens([&b"USER".to_vec(), &b"Content-Length".to_vec(), &b"Content".to_vec(), &b": ".to_vec()]);
+        let tokenizer = DictionaryTokenizer::new(&dict);
+        
+        let stream = TokenStream::tokenize_with(&tokenizer, b"USER USERS xUSER\r\nContent-Length: 5\r\n");
+        assert_eq!(constants(&stream), [&b"USER"[..], b"Content-Length", b": "]);
+        
+        let tokenizer = DictionaryTokenizer::with_tokenizer(&dict, RawTokenizer);
+        let stream = TokenStream::tokenize_with(&tokenizer, b"USER 1,2\r\n");
+        assert_eq!(stream.tokens().len(), 2);
+        assert!(matches!(&stream.tokens()[1], TextToken::Raw(data) if data == b" 1,2\r\n"));
+    }
+    
+    #[test]
+    fn test_retokenize_stage() {
+        let mut state = StdState::new(StdRand::with_seed(0), InMemoryCorpus::new(), InMemoryCorpus::new(), &mut (), &mut ()).unwrap();
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), (), ());
+        let input = PacketBasedInput::new(vec!["USER anonymous\r\nPASS secret\r\n".parse::<TokenStream>().unwrap()]);
+        let id = state.corpus_mut().add(Testcase::new(input)).unwrap();
+        let mut stage = RetokenizeStage::new();
+        
+        stage.perform(&mut fuzzer, &mut (), &mut state, &mut ()).unwrap();
+        assert!(constants(&state.corpus().cloned_input_for_id(id).unwrap().packets()[0]).is_empty());
+        
+        let mut dict = Tokens::new();
+        dict.add_token(&b"USER".to_vec());
+        state.add_metadata(dict);
+        stage.perform(&mut fuzzer, &mut (), &mut state, &mut ()).unwrap();
+        assert_eq!(constants(&state.corpus().cloned_input_for_id(id).unwrap().packets()[0]), [&b"USER"[..]]);
+        
+        /* A dictionary of the same size with a different entry is applied too */
+        let mut dict = Tokens::new();
+        dict.add_token(&b"PASS".to_vec());
+        state.add_metadata(dict);
+        stage.perform(&mut fuzzer, &mut (), &mut state, &mut ()).unwrap();
+        assert_eq!(constants(&state.corpus().cloned_input_for_id(id).unwrap().packets()[0]), [&b"USER"[..], b"PASS"]);
+    }
+}
@@ -0,0 +1,217 @@
+use libafl::prelude::{
+    Stage, Restartable, RetryCountRestartHelper, ExecutesInput, HasObservers, HasCurrentTestcase, HasCurrentCorpusId,
+    HasNamedMetadata, Error, HasFeedback, HasScheduler, Feedback, RemovableScheduler, HasExecutions,
+};
+use libafl_bolts::prelude::{Named, Handle, Handled, MatchName};
+use std::borrow::Cow;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use crate::packets::minimize_current_testcase;
+use crate::tokens::{TokenStream, TextToken, Tokenizer, DefaultTokenizer, number_prefix};
+
+/* Replacements for a token, shortest first */
+fn token_candidates(token: &TextToken) -> Vec<TextToken> {
+    let mut candidates = Vec::new();
+    
+    match token {
+        TextToken::Constant(_) => {},
+        TextToken::Whitespace(_) => candidates.push(TextToken::Whitespace(b" ".to_vec())),
+        TextToken::Number(data) => {
+            let sign = usize::from(matches!(data.first(), Some(b'+') | Some(b'-')));
+            candidates.push(TextToken::Number(b"0".to_vec()));
+            
+            let mut len = 1;
+            
+            while sign + len < data.len() {
+                candidates.push(TextToken::Number(data[..sign + len].to_vec()));
+                len *= 2;
+            }
+        },
+        TextToken::Text(data) => {
+            candidates.push(TextToken::Text(b"a".to_vec()));
+            
+            let mut len = 1;
+            
+            while len < data.len() {
+                candidates.push(TextToken::Text(data[..len].to_vec()));
+                len *= 2;
+            }
+        },
+        TextToken::Hex(data) | TextToken::Octal(data) => {
+            let prefix = number_prefix(data);
+            let with_data = |data: Vec<u8>| {
+                let mut candidate = token.clone_nodata();
+                *candidate.data_mut() = data;
+                candidate
+            };
+            candidates.push(with_data([&data[..prefix], b"0"].concat()));
+            
+            let mut len = 1;
+            
+            while prefix + len < data.len() {
+                candidates.push(with_data(data[..prefix + len].to_vec()));
+                len *= 2;
+            }
+        },
+        TextToken::Float(_) => candidates.push(TextToken::Float(b"0.0".to_vec())),
+        TextToken::Quoted(data) => candidates.push(TextToken::Quoted(vec![data[0], data[0]])),
+        TextToken::Delimiter(_) => {},
+        TextToken::Raw(data) => {
+            let mut len = 1;
+            
+            while len < data.len() {
+                candidates.push(TextToken::Raw(data[..len].to_vec()));
+                len *= 2;
+            }
+        },
+    }
+    
+    candidates.retain(|candidate| candidate.data() != token.data());
+    candidates
+}
+
+/// Makes a [`TokenStream`] smaller on token boundaries as long as `keep` accepts the result:
+/// deletes ranges of tokens, collapses whitespace to a single space and
+/// replaces numbers and text with shorter values.
+/// Constants are never changed.
+pub fn shrink_tokens<T, F>(stream: &mut TokenStream<T>, keep: &mut F) -> Result<(), Error>
+where
+    F: FnMut(&TokenStream<T>) -> Result<bool, Error> + ?Sized,
+{
+    let mut chunk = std::cmp::max(stream.tokens().len() / 2, 1);
+    
+    loop {
+        let mut start = 0;
+        
+        while start < stream.tokens().len() {
+            let end = std::cmp::min(start + chunk, stream.tokens().len());
+            let mut candidate = stream.clone();
+            candidate.tokens_mut().drain(start..end);
+            
+            if keep(&candidate)? {
+                *stream = candidate;
+            } else {
+                start += chunk;
+            }
+        }
+        
+        if chunk == 1 {
+            break;
+        }
+        
+        chunk /= 2;
+    }
+    
+    for idx in 0..stream.tokens().len() {
+        for token in token_candidates(&stream.tokens()[idx]) {
+            let mut candidate = stream.clone();
+            candidate.tokens_mut()[idx] = token;
+            
+            if keep(&candidate)? {
+                *stream = candidate;
+                break;
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+/// A stage that minimizes every [`TokenStream`] testcase of the corpus once with [`shrink_tokens`].
+///
+/// A smaller input is kept if it produces the same hash of the observer `C`,
+/// usually the coverage map. Inputs that crash or time out only have to keep their exit kind.
+/// For a `PacketBasedInput<TokenStream>` use the [`crate::packets::PacketMinimizerStage`],
+/// which shrinks the packets the same way.
+/// `T` is the [`Tokenizer`] of the inputs.
+pub struct TokenMinimizerStage<C, T = DefaultTokenizer> {
+    observer: Handle<C>,
+    phantom: PhantomData<T>,
+}
+
+impl<C, T> TokenMinimizerStage<C, T>
+where
+    C: Handled,
+{
+    pub fn new(observer: &C) -> Self {
+        Self {
+            observer: observer.handle(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<C, T> Named for TokenMinimizerStage<C, T> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("TokenMinimizerStage");
+        &NAME
+    }
+}
+
+impl<C, S, T> Restartable<S> for TokenMinimizerStage<C, T>
+where
+    S: HasNamedMetadata + HasCurrentCorpusId,
+{
+    fn should_restart(&mut self, state: &mut S) -> Result<bool, Error> {
+        /* The stage is not restart-safe */
+        RetryCountRestartHelper::no_retry(state, self.name())
+    }
+    
+    fn clear_progress(&mut self, state: &mut S) -> Result<(), Error> {
+        RetryCountRestartHelper::clear_progress(state, self.name())
+    }
+}
+
+impl<C, E, EM, S, T, Z> Stage<E, EM, S, Z> for TokenMinimizerStage<C, T>
+where
+    C: Hash,
+    E: HasObservers,
+    E::Observers: MatchName,
+    S: HasCurrentTestcase<TokenStream<T>> + HasCurrentCorpusId + HasExecutions,
+    T: Tokenizer + Default,
+    Z: ExecutesInput<E, EM, TokenStream<T>, S> + HasFeedback + HasScheduler<TokenStream<T>, S>,
+    Z::Feedback: Feedback<EM, TokenStream<T>, E::Observers, S>,
+    Z::Scheduler: RemovableScheduler<TokenStream<T>, S>,
+{
+    fn perform(&mut self, fuzzer: &mut Z, executor: &mut E, state: &mut S, manager: &mut EM) -> Result<(), Error> {
+        minimize_current_testcase(&self.observer, fuzzer, executor, state, manager, |input, oracle| shrink_tokens(input, oracle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::{PacketBasedInput, minimize_packets};
+    
+    fn contains(stream: &TokenStream, needle: &[u8]) -> bool {
+        let data: Vec<u8> = stream.tokens().iter().flat_map(|t| t.data().to_vec()).collect();
+        data.windows(needle.len()).any(|w| w == needle)
+    }
+    
+    #[test]
+    fn test_shrink_tokens() {
+        let mut stream = "STOR    home_user_files_0123456789 +004096 MODE\r\n".parse::<TokenStream>().unwrap();
+        
+        /* The target crashes on STOR with any path followed by a number */
+        shrink_tokens(&mut stream, &mut |candidate: &TokenStream| {
+            let tokens = candidate.tokens();
+            Ok(contains(candidate, b"STOR") && tokens.windows(3).any(|w| w[0].is_whitespace() && !w[1].is_number() && w[2].is_number()))
+        }).unwrap();
+        
+        assert_eq!(stream.tokens().len(), 4);
+        assert_eq!(stream.tokens()[1].data(), b" ");
+        assert_eq!(stream.tokens()[2].data(), b"a");
+        assert_eq!(stream.tokens()[3].data(), b"0");
+        
+        let mut input = PacketBasedInput::new(vec![
+            "USER anonymous\r\n".parse::<TokenStream>().unwrap(),
+            "SITE EXEC     /bin/sh\r\n".parse::<TokenStream>().unwrap(),
+        ]);
+        
+        minimize_packets(&mut input, |candidate| {
+            Ok(candidate.packets().iter().any(|p| contains(p, b"EXEC")))
+        }).unwrap();
+        
+        assert_eq!(input.txt_to_vec(usize::MAX).unwrap(), b"EXEC");
+    }
+}
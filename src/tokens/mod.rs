@@ -1,9 +1,15 @@
 mod tokenstream;
 mod mutator;
 mod mutators;
+mod minimize;
+mod tokenizer;
+mod dictionary;
 
 pub(crate) use mutators::*;
 
 pub use tokenstream::*;
 pub use mutator::*;
+pub use minimize::*;
+pub use tokenizer::*;
+pub use dictionary::*;
 pub use mutators::TokenStreamPacketMutator;
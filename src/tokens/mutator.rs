@@ -3,7 +3,7 @@ use libafl::prelude::{
     random_corpus_id, Corpus, HasMetadata, HasRand,
 };
 use libafl_bolts::prelude::{Named, Rand, StdRand, HasLen};
-use crate::tokens::{TokenStream, mutators::*};
+use crate::tokens::{TokenStream, Tokenizer, mutators::*};
 use std::borrow::Cow;
 
 pub(crate) const MUTATOR_STACKS: [usize; 5] = [
@@ -13,10 +13,10 @@ pub(crate) const MUTATOR_STACKS: [usize; 5] = [
     16,
     32,
 ];
-pub(crate) const NUM_MUTATORS: usize = 17;
+pub(crate) const NUM_MUTATORS: usize = 21;
 
 #[inline]
-pub(crate) fn mutate_non_crossover<R, S, const L: usize, const N: usize>(idx: usize, stream: &mut TokenStream, state: &mut S, rand: &mut R, max_tokens: usize) -> bool
+pub(crate) fn mutate_non_crossover<R, S, T, const L: usize, const N: usize>(idx: usize, stream: &mut TokenStream<T>, state: &mut S, rand: &mut R, max_tokens: usize) -> bool
 where
     R: Rand,
     S: HasMetadata,
@@ -28,8 +28,8 @@ where
         3 => mutate_interesting(rand, stream),
         4 => mutate_random_insert(rand, stream, max_tokens),
         5 => mutate_random_replace(rand, stream),
-        6 => mutate_repeat_char::<_, L>(rand, stream),
-        7 => mutate_repeat_token::<_, N>(rand, stream, max_tokens),
+        6 => mutate_repeat_char::<_, _, L>(rand, stream),
+        7 => mutate_repeat_token::<_, _, N>(rand, stream, max_tokens),
         8 => mutate_special_insert(rand, stream),
         9 => mutate_special_replace(rand, stream),
         10 => mutate_split(rand, stream, max_tokens),
@@ -63,6 +63,10 @@ where
                 false
             }
         },
+        17 => mutate_quoted::<_, _, L>(rand, stream),
+        18 => mutate_delimiter(rand, stream, max_tokens),
+        19 => mutate_raw(rand, stream, max_tokens),
+        20 => mutate_arithmetic(rand, stream),
         _ => unreachable!(),
     }
 }
@@ -79,11 +83,12 @@ impl<const M: usize> Named for TokenStreamMutator<M> {
     }
 }
 
-impl<S, const M: usize> Mutator<TokenStream, S> for TokenStreamMutator<M>
+impl<S, T, const M: usize> Mutator<TokenStream<T>, S> for TokenStreamMutator<M>
 where
-    S: HasRand + HasMetadata + HasCorpus<TokenStream>,
+    S: HasRand + HasMetadata + HasCorpus<TokenStream<T>>,
+    T: Tokenizer + Default,
 {
-    fn mutate(&mut self, state: &mut S, input: &mut TokenStream) -> Result<MutationResult, Error> {
+    fn mutate(&mut self, state: &mut S, input: &mut TokenStream<T>) -> Result<MutationResult, Error> {
         self.rand.set_seed(state.rand_mut().next());
         let stack = self.rand.choose(MUTATOR_STACKS).unwrap();
         let mut mutated = false;
@@ -91,7 +96,7 @@ where
         for _ in 0..stack {
             let idx = self.rand.between(0, NUM_MUTATORS + 1);
             mutated |= if idx < NUM_MUTATORS {
-                mutate_non_crossover::<_, _, 16, 4>(idx, input, state, &mut self.rand, M)
+                mutate_non_crossover::<_, _, _, 16, 4>(idx, input, state, &mut self.rand, M)
             } else {
                 let id = random_corpus_id!(state.corpus(), &mut self.rand);
                 
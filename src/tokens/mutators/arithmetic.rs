@@ -0,0 +1,316 @@
+use crate::tokens::{TokenStream, TextToken};
+use libafl_bolts::prelude::Rand;
+use std::cmp::Ordering;
+
+/* Numbers don't grow beyond this many digits, so that repeated multiplications can't blow up the input */
+const MAX_DIGITS: usize = 64;
+
+/* A decimal number of arbitrary length: sign and digit values, most significant first, without leading zeros */
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Decimal {
+    negative: bool,
+    digits: Vec<u8>,
+}
+
+/* How the number was written */
+struct Format {
+    plus: bool,
+    width: usize,
+}
+
+fn trim(mut digits: Vec<u8>) -> Vec<u8> {
+    let zeros = digits.iter().take_while(|d| **d == 0).count();
+    digits.drain(..std::cmp::min(zeros, digits.len() - 1));
+    digits
+}
+
+fn cmp_magnitude(a: &[u8], b: &[u8]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+fn add_magnitude(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(std::cmp::max(a.len(), b.len()) + 1);
+    let mut carry = 0;
+    let mut a = a.iter().rev();
+    let mut b = b.iter().rev();
+    
+    loop {
+        let (x, y) = (a.next(), b.next());
+        
+        if x.is_none() && y.is_none() {
+            break;
+        }
+        
+        let sum = x.copied().unwrap_or(0) + y.copied().unwrap_or(0) + carry;
+        result.push(sum % 10);
+        carry = sum / 10;
+    }
+    
+    if carry > 0 {
+        result.push(carry);
+    }
+    
+    result.reverse();
+    trim(result)
+}
+
+/* Requires a >= b */
+fn sub_magnitude(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    let mut b = b.iter().rev();
+    
+    for x in a.iter().rev() {
+        let mut diff = *x as i8 - b.next().copied().unwrap_or(0) as i8 - borrow;
+        borrow = 0;
+        
+        if diff < 0 {
+            diff += 10;
+            borrow = 1;
+        }
+        
+        result.push(diff as u8);
+    }
+    
+    result.reverse();
+    trim(result)
+}
+
+impl Decimal {
+    fn parse(data: &[u8]) -> Option<(Self, Format)> {
+        let sign = usize::from(matches!(data.first(), Some(b'+') | Some(b'-')));
+        let digits = &data[sign..];
+        
+        if digits.is_empty() || !digits.iter().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        
+        let value = Self {
+            negative: data[0] == b'-',
+            digits: trim(digits.iter().map(|c| c - b'0').collect()),
+        };
+        let format = Format {
+            plus: data[0] == b'+',
+            width: digits.len(),
+        };
+        Some((value.normalize(), format))
+    }
+    
+    fn from_u64(value: u64) -> Self {
+        Self {
+            negative: false,
+            digits: trim(value.to_string().bytes().map(|c| c - b'0').collect()),
+        }
+    }
+    
+    fn is_zero(&self) -> bool {
+        self.digits == [0]
+    }
+    
+    /* There is no negative zero */
+    fn normalize(mut self) -> Self {
+        if self.is_zero() {
+            self.negative = false;
+        }
+        
+        self
+    }
+    
+    fn negate(mut self) -> Self {
+        self.negative = !self.negative;
+        self.normalize()
+    }
+    
+    fn add(&self, other: &Self) -> Self {
+        if self.negative == other.negative {
+            return Self {
+                negative: self.negative,
+                digits: add_magnitude(&self.digits, &other.digits),
+            }.normalize();
+        }
+        
+        match cmp_magnitude(&self.digits, &other.digits) {
+            Ordering::Less => Self {
+                negative: other.negative,
+                digits: sub_magnitude(&other.digits, &self.digits),
+            },
+            _ => Self {
+                negative: self.negative,
+                digits: sub_magnitude(&self.digits, &other.digits),
+            },
+        }.normalize()
+    }
+    
+    fn sub(&self, other: &Self) -> Self {
+        self.add(&other.clone().negate())
+    }
+    
+    fn mul_small(&self, factor: u8) -> Self {
+        let mut result = Vec::with_capacity(self.digits.len() + 3);
+        let mut carry = 0u32;
+        
+        for digit in self.digits.iter().rev() {
+            let product = *digit as u32 * factor as u32 + carry;
+            result.push((product % 10) as u8);
+            carry = product / 10;
+        }
+        
+        while carry > 0 {
+            result.push((carry % 10) as u8);
+            carry /= 10;
+        }
+        
+        result.reverse();
+        Self {
+            negative: self.negative,
+            digits: trim(result),
+        }.normalize()
+    }
+    
+    /* The smallest power of two that is larger than the magnitude */
+    fn next_power_of_two(&self) -> Self {
+        let mut power = Self::from_u64(1);
+        
+        while cmp_magnitude(&power.digits, &self.digits) != Ordering::Greater {
+            power = power.mul_small(2);
+        }
+        
+        power
+    }
+    
+    /* Keeps an explicit plus sign and pads with zeros to the old width */
+    fn format(&self, format: &Format) -> Vec<u8> {
+        let mut data = Vec::with_capacity(format.width + 1);
+        
+        if self.negative {
+            data.push(b'-');
+        } else if format.plus {
+            data.push(b'+');
+        }
+        
+        data.extend(std::iter::repeat_n(b'0', format.width.saturating_sub(self.digits.len())));
+        
+        data.extend(self.digits.iter().map(|d| d + b'0'));
+        data
+    }
+}
+
+fn apply<R: Rand>(rand: &mut R, value: &Decimal) -> Decimal {
+    match rand.between(0, 6) {
+        0 => value.add(&Decimal::from_u64(rand.between(1, 16) as u64)),
+        1 => value.sub(&Decimal::from_u64(rand.between(1, 16) as u64)),
+        2 => value.mul_small(2),
+        3 => value.mul_small(rand.between(3, 10) as u8),
+        4 => value.clone().negate(),
+        5 => {
+            /* Off by one */
+            if rand.coinflip(0.5) {
+                value.add(&Decimal::from_u64(1))
+            } else {
+                value.sub(&Decimal::from_u64(1))
+            }
+        },
+        6 => {
+            /* Around the next boundary */
+            let boundary = value.next_power_of_two();
+            let boundary = if value.negative { boundary.negate() } else { boundary };
+            
+            match rand.between(0, 2) {
+                0 => boundary.sub(&Decimal::from_u64(1)),
+                1 => boundary,
+                2 => boundary.add(&Decimal::from_u64(1)),
+                _ => unreachable!(),
+            }
+        },
+        _ => unreachable!(),
+    }
+}
+
+pub fn mutate_arithmetic<R: Rand, T>(rand: &mut R, stream: &mut TokenStream<T>) -> bool {
+    let numbers = stream.tokens().iter().enumerate().filter(|(_, t)| t.is_number()).map(|(i, _)| i);
+    
+    let Some(idx) = rand.choose(numbers) else {
+        return false;
+    };
+    let TextToken::Number(data) = &mut stream.tokens_mut()[idx] else {
+        unreachable!()
+    };
+    let Some((value, format)) = Decimal::parse(data) else {
+        return false;
+    };
+    
+    let result = apply(rand, &value);
+    
+    if result.digits.len() > MAX_DIGITS && result.digits.len() > value.digits.len() {
+        return false;
+    }
+    
+    *data = result.format(&format);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libafl_bolts::prelude::{StdRand, current_nanos};
+    
+    fn calc<F: Fn(&Decimal) -> Decimal>(data: &[u8], f: F) -> Vec<u8> {
+        let (value, format) = Decimal::parse(data).unwrap();
+        f(&value).format(&format)
+    }
+    
+    #[test]
+    fn test_arithmetic() {
+        let one = Decimal::from_u64(1);
+        
+        assert_eq!(calc(b"+007", |v| v.add(&one)), b"+008");
+        assert_eq!(calc(b"0099", |v| v.add(&one)), b"0100");
+        assert_eq!(calc(b"5", |v| v.sub(&Decimal::from_u64(7))), b"-2");
+        assert_eq!(calc(b"-2", |v| v.add(&Decimal::from_u64(2))), b"0");
+        assert_eq!(calc(b"+5", |v| v.clone().negate()), b"-5");
+        assert_eq!(calc(b"-0", |v| v.clone().negate()), b"0");
+        assert_eq!(calc(b"99999999999999999999999", |v| v.add(&one)), b"100000000000000000000000");
+        assert_eq!(calc(b"-123456789012345678901234567890", |v| v.mul_small(2)), b"-246913578024691357802469135780");
+        assert_eq!(calc(b"255", |v| v.next_power_of_two()), b"256");
+        assert_eq!(calc(b"18446744073709551615", |v| v.next_power_of_two()), b"18446744073709551616");
+        
+        let mut rand = StdRand::with_seed(current_nanos());
+        let stream = "SIZE +004096 -17 18446744073709551615\r\n".parse::<TokenStream>().unwrap();
+        assert_eq!(stream.tokens().iter().filter(|t| t.is_number()).count(), 3);
+        
+        for _ in 0..100 {
+            let mut stream_mut = stream.clone();
+            
+            if !mutate_arithmetic(&mut rand, &mut stream_mut) {
+                continue;
+            }
+            
+            /* Only a single number changes and it stays a number */
+            assert_eq!(stream_mut.tokens().len(), stream.tokens().len());
+            let mut changed = 0;
+            
+            for (token, old) in stream_mut.tokens().iter().zip(stream.tokens()) {
+                assert!(token.verify(), "{token:?}");
+                
+                if token.data() != old.data() {
+                    assert!(token.is_number() && old.is_number());
+                    changed += 1;
+                }
+            }
+            
+            assert!(changed <= 1);
+        }
+        
+        /* Repeated mutations don't grow a number beyond MAX_DIGITS */
+        let mut stream = "9".repeat(MAX_DIGITS).parse::<TokenStream>().unwrap();
+        
+        for _ in 0..1000 {
+            mutate_arithmetic(&mut rand, &mut stream);
+            assert_eq!(stream.tokens().len(), 1);
+            assert!(stream.tokens()[0].is_number());
+            assert!(stream.tokens()[0].len() <= MAX_DIGITS + 1);
+        }
+        
+        assert!(!mutate_arithmetic(&mut rand, &mut "USER a".parse::<TokenStream>().unwrap()));
+    }
+}
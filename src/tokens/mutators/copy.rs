@@ -1,7 +1,7 @@
 use crate::tokens::{TokenStream, mutators::common::random_range};
 use libafl_bolts::prelude::{Rand, HasLen};
 
-pub fn mutate_copy<R: Rand>(rand: &mut R, stream: &mut TokenStream, max_len: usize) -> bool {
+pub fn mutate_copy<R: Rand, T>(rand: &mut R, stream: &mut TokenStream<T>, max_len: usize) -> bool {
     if stream.is_empty() || stream.len() >= max_len {
         return false;
     }
@@ -1,7 +1,7 @@
 use crate::tokens::{TokenStream, mutators::common::random_range};
 use libafl_bolts::prelude::{Rand, HasLen};
 
-pub fn mutate_crossover_replace<R: Rand>(rand: &mut R, stream: &mut TokenStream, other: &TokenStream, max_len: usize) -> bool {
+pub fn mutate_crossover_replace<R: Rand, T>(rand: &mut R, stream: &mut TokenStream<T>, other: &TokenStream<T>, max_len: usize) -> bool {
     if stream.is_empty() || other.is_empty() || stream.len() >= max_len {
         return false;
     }
@@ -15,7 +15,7 @@ pub fn mutate_crossover_replace<R: Rand>(rand: &mut R, stream: &mut TokenStream,
     true
 }
 
-pub fn mutate_crossover_insert<R: Rand>(rand: &mut R, stream: &mut TokenStream, other: &TokenStream, max_len: usize) -> bool {
+pub fn mutate_crossover_insert<R: Rand, T>(rand: &mut R, stream: &mut TokenStream<T>, other: &TokenStream<T>, max_len: usize) -> bool {
     if stream.is_empty() || other.is_empty() || stream.len() >= max_len {
         return false;
     }
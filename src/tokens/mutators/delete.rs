@@ -1,7 +1,7 @@
 use crate::tokens::{TokenStream, mutators::common::random_range};
 use libafl_bolts::prelude::{Rand, HasLen};
 
-pub fn mutate_delete<R: Rand>(rand: &mut R, stream: &mut TokenStream) -> bool {
+pub fn mutate_delete<R: Rand, T>(rand: &mut R, stream: &mut TokenStream<T>) -> bool {
     if stream.is_empty() {
         return false;
     }
@@ -0,0 +1,60 @@
+use crate::tokens::{TokenStream, TextToken, DELIMITERS};
+use libafl_bolts::prelude::{Rand, HasLen};
+
+pub fn mutate_delimiter<R: Rand, T>(rand: &mut R, stream: &mut TokenStream<T>, max_len: usize) -> bool {
+    if stream.is_empty() {
+        return false;
+    }
+    
+    let start = rand.between(0, stream.len() - 1);
+    let Some(idx) = stream.tokens()[start..].iter().position(|t| matches!(t, TextToken::Delimiter(_))) else {
+        return false;
+    };
+    let idx = start + idx;
+    let c = rand.choose(DELIMITERS).unwrap();
+    
+    if stream.len() < max_len && rand.coinflip(0.5) {
+        stream.tokens_mut().insert(idx + 1, TextToken::Delimiter(vec![c]));
+    } else {
+        *stream.tokens_mut()[idx].data_mut() = vec![c];
+    }
+    
+    debug_assert!(stream.len() <= max_len);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libafl_bolts::prelude::{StdRand, current_nanos};
+    
+    fn others(stream: &TokenStream) -> Vec<(std::mem::Discriminant<TextToken>, &[u8])> {
+        stream.tokens().iter().filter(|t| !matches!(t, TextToken::Delimiter(_))).map(|t| (std::mem::discriminant(t), t.data())).collect()
+    }
+    
+    fn delimiters(stream: &TokenStream) -> usize {
+        stream.tokens().iter().filter(|t| matches!(t, TextToken::Delimiter(_))).count()
+    }
+    
+    #[test]
+    fn test_delimiter() {
+        let mut rand = StdRand::with_seed(current_nanos());
+        let stream = "PORT 127,0,0,1,80,80\r\n".parse::<TokenStream>().unwrap();
+        
+        for _ in 0..100 {
+            let mut stream_mut = stream.clone();
+            
+            if !mutate_delimiter(&mut rand, &mut stream_mut, 16) {
+                continue;
+            }
+            
+            /* Every delimiter stays a single delimiter byte and nothing else changes */
+            assert!(stream_mut.tokens().iter().all(|t| t.verify()), "{stream_mut:?}");
+            assert_eq!(others(&stream_mut), others(&stream));
+            assert!(delimiters(&stream_mut) - delimiters(&stream) <= 1);
+            assert!(stream_mut.len() <= 16);
+        }
+        
+        assert!(!mutate_delimiter(&mut rand, &mut "USER a".parse::<TokenStream>().unwrap(), 16));
+    }
+}
@@ -2,7 +2,7 @@ use crate::tokens::{TokenStream, TextToken};
 use libafl_bolts::prelude::{Rand, HasLen};
 use libafl::prelude::Tokens;
 
-pub fn mutate_dict_insert<R: Rand>(rand: &mut R, stream: &mut TokenStream, dict: &Tokens, max_len: usize) -> bool {
+pub fn mutate_dict_insert<R: Rand, T>(rand: &mut R, stream: &mut TokenStream<T>, dict: &Tokens, max_len: usize) -> bool {
     if dict.is_empty() || stream.len() >= max_len {
         return false;
     }
@@ -27,7 +27,7 @@ pub fn mutate_dict_insert<R: Rand>(rand: &mut R, stream: &mut TokenStream, dict:
     true
 }
 
-pub fn mutate_dict_replace<R: Rand>(rand: &mut R, stream: &mut TokenStream, dict: &Tokens) -> bool {
+pub fn mutate_dict_replace<R: Rand, T>(rand: &mut R, stream: &mut TokenStream<T>, dict: &Tokens) -> bool {
     if dict.is_empty() || stream.is_empty() {
         return false;
     }
@@ -1,8 +1,9 @@
-use crate::tokens::{TokenStream, TextToken};
-use libafl_bolts::prelude::{Rand, HasLen};
+use crate::tokens::{TokenStream, TextToken, DELIMITERS, number_prefix};
+use libafl_bolts::prelude::{Rand, HasLen, nonzero};
 
 const WHITESPACE: [u8; 6] = [b' ', b'\t', b'\n', 0x0b, 0x0c, b'\r'];
 const DIGITS: [u8; 10] = [b'0', b'1', b'2', b'3', b'4', b'5', b'6', b'7', b'8', b'9'];
+const HEX_DIGITS: [u8; 16] = [b'0', b'1', b'2', b'3', b'4', b'5', b'6', b'7', b'8', b'9', b'a', b'b', b'c', b'd', b'e', b'f'];
 const TEXT_ALLOW_MAP: [bool; 256] = [true, true, true, true, true, true, true, true, true, false, false, false, false, false, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, false, true, true, true, true, true, true, true, true, true, true, false, true, false, true, true, false, false, false, false, false, false, false, false, false, false, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, true, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false];
 
 #[inline]
@@ -14,7 +15,7 @@ fn find_valid_byte(mut c: u8) -> u8 {
     c
 }
 
-pub fn mutate_flip<R: Rand>(rand: &mut R, stream: &mut TokenStream) -> bool {
+pub fn mutate_flip<R: Rand, T>(rand: &mut R, stream: &mut TokenStream<T>) -> bool {
     if stream.is_empty() {
         return false;
     }
@@ -58,6 +59,66 @@ pub fn mutate_flip<R: Rand>(rand: &mut R, stream: &mut TokenStream) -> bool {
             let new_value = (rand.next() as u8) & 0x7F;
             data[idx] = find_valid_byte(new_value);
         },
+        TextToken::Hex(data) => {
+            let prefix = number_prefix(data);
+            
+            if prefix >= data.len() {
+                return false;
+            }
+            
+            let idx = rand.between(prefix, data.len() - 1);
+            
+            if data.iter().any(|c| c.is_ascii_uppercase() && *c != b'X') {
+                data[idx] = rand.choose(HEX_DIGITS).unwrap().to_ascii_uppercase();
+            } else {
+                data[idx] = rand.choose(HEX_DIGITS).unwrap();
+            }
+        },
+        TextToken::Octal(data) => {
+            let prefix = number_prefix(data);
+            
+            if prefix >= data.len() {
+                return false;
+            }
+            
+            let idx = rand.between(prefix, data.len() - 1);
+            data[idx] = rand.choose(&DIGITS[..8]).copied().unwrap();
+        },
+        TextToken::Float(data) => {
+            let Some(idx) = rand.choose(data.iter().enumerate().filter(|(_, c)| c.is_ascii_digit()).map(|(i, _)| i)) else {
+                return false;
+            };
+            data[idx] = rand.choose(DIGITS).unwrap();
+        },
+        TextToken::Quoted(data) => {
+            if data.len() < 3 {
+                return false;
+            }
+            
+            let quote = data[0];
+            
+            /* Backslashes stay where they are so that the escapes don't change */
+            let Some(idx) = rand.choose((1..data.len() - 1).filter(|i| data[*i] != b'\\')) else {
+                return false;
+            };
+            let new_value = rand.between(0x20, 0x7E) as u8;
+            data[idx] = if new_value == quote || new_value == b'\\' { b'_' } else { new_value };
+        },
+        TextToken::Delimiter(data) => {
+            if data.is_empty() {
+                return false;
+            }
+            
+            data[0] = rand.choose(DELIMITERS).unwrap();
+        },
+        TextToken::Raw(data) => {
+            if data.is_empty() {
+                return false;
+            }
+            
+            let idx = rand.between(0, data.len() - 1);
+            data[idx] ^= 1 << rand.below(nonzero!(8));
+        },
     }
     
     true
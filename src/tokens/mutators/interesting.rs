@@ -1,4 +1,4 @@
-use crate::tokens::{TokenStream, TextToken, mutators::common::copy_vec};
+use crate::tokens::{TokenStream, TextToken, number_prefix, mutators::common::copy_vec};
 use libafl_bolts::prelude::{Rand, HasLen};
 
 const INTERESTING: [&[u8]; 33] = [
@@ -51,7 +51,83 @@ const INTERESTING: [&[u8]; 33] = [
     b"100663045",
 ];
 
-pub fn mutate_interesting<R: Rand>(rand: &mut R, stream: &mut TokenStream) -> bool {
+const INTERESTING_HEX: [&[u8]; 17] = [
+    b"0",
+    b"1",
+    b"7f",
+    b"80",
+    b"ff",
+    b"100",
+    b"7fff",
+    b"8000",
+    b"ffff",
+    b"10000",
+    b"7fffffff",
+    b"80000000",
+    b"ffffffff",
+    b"100000000",
+    b"7fffffffffffffff",
+    b"8000000000000000",
+    b"ffffffffffffffff",
+];
+
+const INTERESTING_OCTAL: [&[u8]; 14] = [
+    b"0",
+    b"1",
+    b"7",
+    b"10",
+    b"377",
+    b"400",
+    // file modes
+    b"644",
+    b"755",
+    b"777",
+    b"4777",
+    b"7777",
+    b"177777",
+    // 0xFFFFFFFF
+    b"37777777777",
+    // 0xFFFFFFFFFFFFFFFF
+    b"1777777777777777777777",
+];
+
+const INTERESTING_FLOAT: [&[u8]; 17] = [
+    b"0.0",
+    b"-0.0",
+    b"1.0",
+    b"-1.0",
+    b"0.5",
+    b"1e308",
+    b"1e309",
+    b"-1e309",
+    b"1e-308",
+    b"1e-400",
+    // f64
+    b"1.7976931348623157e308",
+    b"2.2250738585072014e-308",
+    b"4.9e-324",
+    // f32
+    b"3.4028235e38",
+    b"3.4028236e38",
+    b"1.1754944e-38",
+    // 2^53 + 1
+    b"9007199254740993.0",
+];
+
+/* Keeps the sign and the prefix of hex and octal numbers */
+fn replace_digits(data: &mut Vec<u8>, digits: &[u8]) {
+    let prefix = number_prefix(data);
+    let uppercase = data[prefix..].iter().any(|c| c.is_ascii_uppercase());
+    data.truncate(prefix);
+    
+    if uppercase {
+        data.extend(digits.iter().map(|c| c.to_ascii_uppercase()));
+    } else {
+        data.extend_from_slice(digits);
+    }
+}
+
+pub fn mutate_interesting<R: Rand, T>(rand: &mut R, stream: &mut TokenStream<T>) -> bool {
     if stream.is_empty() {
         return false;
     }
@@ -59,10 +135,15 @@ pub fn mutate_interesting<R: Rand>(rand: &mut R, stream: &mut TokenStream) -> bo
     let start = rand.between(0, stream.len() - 1);
     
     for token in &mut stream.tokens_mut()[start..] {
-        if let TextToken::Number(data) = token {
-            copy_vec(data, rand.choose(INTERESTING).unwrap());
-            return true;
+        match token {
+            TextToken::Number(data) => copy_vec(data, rand.choose(INTERESTING).unwrap()),
+            TextToken::Hex(data) => replace_digits(data, rand.choose(INTERESTING_HEX).unwrap()),
+            TextToken::Octal(data) => replace_digits(data, rand.choose(INTERESTING_OCTAL).unwrap()),
+            TextToken::Float(data) => copy_vec(data, rand.choose(INTERESTING_FLOAT).unwrap()),
+            _ => continue,
         }
+        
+        return true;
     }
     
     false
@@ -12,6 +12,10 @@ mod dict;
 mod flip;
 mod truncate;
 mod packet;
+mod quoted;
+mod delimiter;
+mod raw;
+mod arithmetic;
 
 pub use split::*;
 pub use crossover::*;
@@ -26,6 +30,10 @@ pub use dict::*;
 pub use flip::*;
 pub use truncate::*;
 pub use packet::*;
+pub use quoted::*;
+pub use delimiter::*;
+pub use raw::*;
+pub use arithmetic::*;
 
 #[cfg(test)]
 mod tests {
@@ -55,7 +63,7 @@ mod tests {
             let mut stream = stream.clone();
             
             for _ in 0..1000 {
-                let mutation = rand.between(0, 17);
+                let mutation = rand.between(0, 22);
                 
                 let mutated = match mutation {
                     0 => mutate_copy(&mut rand, &mut stream, MAX_LEN),
@@ -74,8 +82,8 @@ mod tests {
                     7 => mutate_interesting(&mut rand, &mut stream),
                     8 => mutate_random_insert(&mut rand, &mut stream, MAX_LEN),
                     9 => mutate_random_replace(&mut rand, &mut stream),
-                    10 => mutate_repeat_char::<_, 8>(&mut rand, &mut stream),
-                    11 => mutate_repeat_token::<_, 8>(&mut rand, &mut stream, MAX_LEN),
+                    10 => mutate_repeat_char::<_, _, 8>(&mut rand, &mut stream),
+                    11 => mutate_repeat_token::<_, _, 8>(&mut rand, &mut stream, MAX_LEN),
                     12 => mutate_special_insert(&mut rand, &mut stream),
                     13 => mutate_special_replace(&mut rand, &mut stream),
                     14 => mutate_split(&mut rand, &mut stream, MAX_LEN),
@@ -83,6 +91,10 @@ mod tests {
                     16 => mutate_swap_tokens(&mut rand, &mut stream),
                     17 => mutate_swap_words(&mut rand, &mut stream),
                     18 => mutate_truncate(&mut rand, &mut stream),
+                    19 => mutate_quoted::<_, _, 8>(&mut rand, &mut stream),
+                    20 => mutate_delimiter(&mut rand, &mut stream, MAX_LEN),
+                    21 => mutate_raw(&mut rand, &mut stream, MAX_LEN),
+                    22 => mutate_arithmetic(&mut rand, &mut stream),
                     _ => unreachable!(),
                 };
                 
@@ -112,7 +124,7 @@ mod tests {
         const MAX_LEN: usize = 128;
         
         for _ in 0..10 {
-            match rand.between(0, 17) {
+            match rand.between(0, 22) {
                 0 => mutate_copy(&mut rand, &mut stream, MAX_LEN),
                 1 => {
                     let other = stream.clone();
@@ -129,8 +141,8 @@ mod tests {
                 7 => mutate_interesting(&mut rand, &mut stream),
                 8 => mutate_random_insert(&mut rand, &mut stream, MAX_LEN),
                 9 => mutate_random_replace(&mut rand, &mut stream),
-                10 => mutate_repeat_char::<_, 8>(&mut rand, &mut stream),
-                11 => mutate_repeat_token::<_, 8>(&mut rand, &mut stream, MAX_LEN),
+                10 => mutate_repeat_char::<_, _, 8>(&mut rand, &mut stream),
+                11 => mutate_repeat_token::<_, _, 8>(&mut rand, &mut stream, MAX_LEN),
                 12 => mutate_special_insert(&mut rand, &mut stream),
                 13 => mutate_special_replace(&mut rand, &mut stream),
                 14 => mutate_split(&mut rand, &mut stream, MAX_LEN),
@@ -138,6 +150,10 @@ mod tests {
                 16 => mutate_swap_tokens(&mut rand, &mut stream),
                 17 => mutate_swap_words(&mut rand, &mut stream),
                 18 => mutate_truncate(&mut rand, &mut stream),
+                19 => mutate_quoted::<_, _, 8>(&mut rand, &mut stream),
+                20 => mutate_delimiter(&mut rand, &mut stream, MAX_LEN),
+                21 => mutate_raw(&mut rand, &mut stream, MAX_LEN),
+                22 => mutate_arithmetic(&mut rand, &mut stream),
                 _ => unreachable!(),
             };
         }
@@ -150,7 +166,7 @@ mod tests {
         let mut buffer = vec![0; total_len];
         stream.serialize_content(&mut buffer);
         
-        println!("{}", std::str::from_utf8(&buffer).unwrap());
+        println!("{}", String::from_utf8_lossy(&buffer));
     }
     
     #[test]
@@ -164,7 +180,7 @@ mod tests {
         const MAX_LEN: usize = 128;
         
         for _ in 0..2 {
-            match rand.between(0, 17) {
+            match rand.between(0, 22) {
                 0 => mutate_copy(&mut rand, &mut stream, MAX_LEN),
                 1 => {
                     let other = stream.clone();
@@ -181,8 +197,8 @@ mod tests {
                 7 => mutate_interesting(&mut rand, &mut stream),
                 8 => mutate_random_insert(&mut rand, &mut stream, MAX_LEN),
                 9 => mutate_random_replace(&mut rand, &mut stream),
-                10 => mutate_repeat_char::<_, 8>(&mut rand, &mut stream),
-                11 => mutate_repeat_token::<_, 8>(&mut rand, &mut stream, MAX_LEN),
+                10 => mutate_repeat_char::<_, _, 8>(&mut rand, &mut stream),
+                11 => mutate_repeat_token::<_, _, 8>(&mut rand, &mut stream, MAX_LEN),
                 12 => mutate_special_insert(&mut rand, &mut stream),
                 13 => mutate_special_replace(&mut rand, &mut stream),
                 14 => mutate_split(&mut rand, &mut stream, MAX_LEN),
@@ -190,6 +206,10 @@ mod tests {
                 16 => mutate_swap_tokens(&mut rand, &mut stream),
                 17 => mutate_swap_words(&mut rand, &mut stream),
                 18 => mutate_truncate(&mut rand, &mut stream),
+                19 => mutate_quoted::<_, _, 8>(&mut rand, &mut stream),
+                20 => mutate_delimiter(&mut rand, &mut stream, MAX_LEN),
+                21 => mutate_raw(&mut rand, &mut stream, MAX_LEN),
+                22 => mutate_arithmetic(&mut rand, &mut stream),
                 _ => unreachable!(),
             };
         }
@@ -202,6 +222,6 @@ mod tests {
         let mut buffer = vec![0; total_len];
         stream.serialize_content(&mut buffer);
         
-        println!("{:?}", std::str::from_utf8(&buffer).unwrap());
+        println!("{:?}", String::from_utf8_lossy(&buffer));
     }
 }
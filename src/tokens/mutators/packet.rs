@@ -18,11 +18,12 @@ impl<const M: usize> Default for TokenStreamPacketMutator<M> {
     }
 }
 
-impl<const M: usize, S> PacketMutator<TokenStream, S> for TokenStreamPacketMutator<M>
+impl<const M: usize, S, T> PacketMutator<TokenStream<T>, S> for TokenStreamPacketMutator<M>
 where
-    S: HasRand + HasMetadata + HasCorpus<PacketBasedInput<TokenStream>>,
+    S: HasRand + HasMetadata + HasCorpus<PacketBasedInput<TokenStream<T>>>,
+    T: Tokenizer + Default,
 {
-    fn mutate_packet(&mut self, state: &mut S, packet: &mut TokenStream) -> Result<MutationResult, Error> {
+    fn mutate_packet(&mut self, state: &mut S, packet: &mut TokenStream<T>) -> Result<MutationResult, Error> {
         self.rand.set_seed(state.rand_mut().next());
         let stack = state.rand_mut().choose(MUTATOR_STACKS).unwrap();
         let mut mutated = false;
@@ -31,7 +32,7 @@ where
             let m = self.rand.between(0, NUM_MUTATORS + 1);
             
             mutated |= if m < NUM_MUTATORS {
-                mutate_non_crossover::<_, _, 16, 4>(m, packet, state, &mut self.rand, M)
+                mutate_non_crossover::<_, _, _, 16, 4>(m, packet, state, &mut self.rand, M)
             } else {
                 let idx = random_corpus_id!(state.corpus(), &mut self.rand);
                 
@@ -0,0 +1,108 @@
+use crate::tokens::{TokenStream, TextToken};
+use libafl_bolts::prelude::{Rand, HasLen};
+
+const ESCAPES: [&[u8]; 9] = [
+    b"\\\\",
+    b"\\n",
+    b"\\r",
+    b"\\t",
+    b"\\0",
+    b"\\x00",
+    b"\\xff",
+    b"\\u0000",
+    b"%s%n",
+];
+
+/* Positions inside the quotes that don't cut an escape sequence */
+fn boundaries(data: &[u8]) -> Vec<usize> {
+    let mut boundaries = Vec::new();
+    let mut i = 1;
+    
+    while i < data.len() {
+        boundaries.push(i);
+        i += if data[i] == b'\\' { 2 } else { 1 };
+    }
+    
+    boundaries
+}
+
+pub fn mutate_quoted<R: Rand, T, const AMNT: usize>(rand: &mut R, stream: &mut TokenStream<T>) -> bool {
+    if stream.is_empty() {
+        return false;
+    }
+    
+    let start = rand.between(0, stream.len() - 1);
+    
+    for token in &mut stream.tokens_mut()[start..] {
+        let TextToken::Quoted(data) = token else {
+            continue;
+        };
+        
+        if data.len() < 2 {
+            continue;
+        }
+        
+        let quote = data[0];
+        
+        match rand.between(0, 2) {
+            0 => {
+                if data.len() == 2 {
+                    return false;
+                }
+                
+                data.truncate(1);
+                data.push(quote);
+            },
+            1 => {
+                let idx = rand.choose(boundaries(data)).unwrap();
+                data.splice(idx..idx, vec![b'A'; AMNT]);
+            },
+            2 => {
+                let idx = rand.choose(boundaries(data)).unwrap();
+                
+                if rand.coinflip(0.5) {
+                    data.splice(idx..idx, [b'\\', quote]);
+                } else {
+                    data.splice(idx..idx, rand.choose(ESCAPES).unwrap().iter().copied());
+                }
+            },
+            _ => unreachable!(),
+        }
+        
+        return true;
+    }
+    
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libafl_bolts::prelude::{StdRand, current_nanos};
+    
+    #[test]
+    fn test_quoted() {
+        let mut rand = StdRand::with_seed(current_nanos());
+        let stream = "SET key=\"a \\\"quoted\\\" value\"\r\n".parse::<TokenStream>().unwrap();
+        
+        for _ in 0..100 {
+            let mut stream_mut = stream.clone();
+            
+            if !mutate_quoted::<_, _, 8>(&mut rand, &mut stream_mut) {
+                continue;
+            }
+            
+            assert_eq!(stream_mut.len(), stream.len());
+            
+            for (token, old) in stream_mut.tokens().iter().zip(stream.tokens()) {
+                assert!(token.verify(), "{token:?}");
+                
+                /* The string keeps its quotes, all other tokens stay as they are */
+                match token {
+                    TextToken::Quoted(data) => assert!(data.len() >= 2 && data[0] == b'"' && data[data.len() - 1] == b'"'),
+                    _ => assert!(std::mem::discriminant(token) == std::mem::discriminant(old) && token.data() == old.data()),
+                }
+            }
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use crate::tokens::{TokenStream, TextToken};
 use libafl_bolts::prelude::{Rand, HasLen};
 
-pub fn mutate_random_insert<R: Rand>(rand: &mut R, stream: &mut TokenStream, max_len: usize) -> bool {
+pub fn mutate_random_insert<R: Rand, T>(rand: &mut R, stream: &mut TokenStream<T>, max_len: usize) -> bool {
     if stream.len() >= max_len {
         return false;
     }
@@ -19,7 +19,7 @@ pub fn mutate_random_insert<R: Rand>(rand: &mut R, stream: &mut TokenStream, max
     true
 }
 
-pub fn mutate_random_replace<R: Rand>(rand: &mut R, stream: &mut TokenStream) -> bool {
+pub fn mutate_random_replace<R: Rand, T>(rand: &mut R, stream: &mut TokenStream<T>) -> bool {
     if stream.is_empty() {
         return false;
     }
@@ -0,0 +1,105 @@
+use crate::tokens::{TokenStream, TextToken};
+use libafl_bolts::prelude::{Rand, HasLen};
+
+const INTERESTING_RAW: [&[u8]; 13] = [
+    b"\x00",
+    b"\xff",
+    b"\x80",
+    b"\xfe",
+    // Latin-1 e with acute
+    b"\xe9",
+    // UTF-8 e with acute
+    b"\xc3\xa9",
+    // Truncated UTF-8 sequence
+    b"\xc3",
+    // Overlong NUL and slash
+    b"\xc0\x80",
+    b"\xc0\xaf",
+    b"\xe0\x80\xaf",
+    // Surrogate
+    b"\xed\xa0\x80",
+    // BOM
+    b"\xef\xbb\xbf",
+    // Beyond U+10FFFF
+    b"\xf4\x90\x80\x80",
+];
+
+pub fn mutate_raw<R: Rand, T>(rand: &mut R, stream: &mut TokenStream<T>, max_len: usize) -> bool {
+    let raw = stream.tokens().iter().enumerate().filter(|(_, t)| matches!(t, TextToken::Raw(_))).map(|(i, _)| i);
+    let raw = rand.choose(raw);
+    
+    /* Inputs without raw tokens get some non-ASCII bytes inserted */
+    if raw.is_none() || (stream.len() < max_len && rand.coinflip(0.25)) {
+        if stream.len() >= max_len {
+            return false;
+        }
+        
+        let idx = rand.between(0, stream.len());
+        let new_elem = TextToken::Raw(rand.choose(INTERESTING_RAW).unwrap().to_vec());
+        stream.tokens_mut().insert(idx, new_elem);
+        
+        debug_assert!(stream.len() <= max_len);
+        return true;
+    }
+    
+    let data = stream.tokens_mut()[raw.unwrap()].data_mut();
+    
+    if data.is_empty() {
+        return false;
+    }
+    
+    let idx = rand.between(0, data.len() - 1);
+    
+    match rand.between(0, 2) {
+        0 => data[idx] = rand.next() as u8,
+        1 => {
+            data.splice(idx..idx, rand.choose(INTERESTING_RAW).unwrap().iter().copied());
+        },
+        2 => {
+            if data.len() < 2 {
+                return false;
+            }
+            
+            data.remove(idx);
+        },
+        _ => unreachable!(),
+    }
+    
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::Packet;
+    use libafl_bolts::prelude::{StdRand, current_nanos};
+    
+    fn others(stream: &TokenStream) -> Vec<(std::mem::Discriminant<TextToken>, &[u8])> {
+        stream.tokens().iter().filter(|t| !matches!(t, TextToken::Raw(_))).map(|t| (std::mem::discriminant(t), t.data())).collect()
+    }
+    
+    #[test]
+    fn test_raw() {
+        let mut rand = StdRand::with_seed(current_nanos());
+        let stream = TokenStream::from_bytes(b"USER j\xc3\xb6rg\r\nSTOR \xff\xfe\x00x\r\n");
+        assert_eq!(stream.content_to_vec(), b"USER j\xc3\xb6rg\r\nSTOR \xff\xfe\x00x\r\n");
+        
+        for _ in 0..100 {
+            let mut stream_mut = stream.clone();
+            
+            if !mutate_raw(&mut rand, &mut stream_mut, 32) {
+                continue;
+            }
+            
+            /* Only raw tokens change or get inserted */
+            assert!(stream_mut.tokens().iter().all(|t| t.verify() && !t.data().is_empty()));
+            assert_eq!(others(&stream_mut), others(&stream));
+            assert!(stream_mut.len() <= 32);
+        }
+        
+        /* Streams without raw tokens get one */
+        let mut stream = "USER a\r\n".parse::<TokenStream>().unwrap();
+        assert!(mutate_raw(&mut rand, &mut stream, 32));
+        assert_eq!(stream.tokens().iter().filter(|t| matches!(t, TextToken::Raw(_))).count(), 1);
+    }
+}
@@ -1,7 +1,7 @@
 use crate::tokens::TokenStream;
 use libafl_bolts::prelude::{Rand, HasLen};
 
-pub fn mutate_repeat_token<R: Rand, const AMNT: usize>(rand: &mut R, stream: &mut TokenStream, max_len: usize) -> bool {
+pub fn mutate_repeat_token<R: Rand, T, const AMNT: usize>(rand: &mut R, stream: &mut TokenStream<T>, max_len: usize) -> bool {
     if stream.is_empty() || stream.len() >= max_len {
         return false;
     }
@@ -24,7 +24,7 @@ pub fn mutate_repeat_token<R: Rand, const AMNT: usize>(rand: &mut R, stream: &mu
     true
 }
 
-pub fn mutate_repeat_char<R: Rand, const AMNT: usize>(rand: &mut R, stream: &mut TokenStream) -> bool {
+pub fn mutate_repeat_char<R: Rand, T, const AMNT: usize>(rand: &mut R, stream: &mut TokenStream<T>) -> bool {
     if stream.is_empty() {
         return false;
     }
@@ -50,6 +50,19 @@ pub fn mutate_repeat_char<R: Rand, const AMNT: usize>(rand: &mut R, stream: &mut
     }
     
     let c = elem.data()[idx];
+    
+    if elem.is_structured() {
+        let mut repeated = elem.clone();
+        repeated.data_mut().splice(idx..idx, vec![c; n]);
+        
+        if !repeated.verify() {
+            return false;
+        }
+        
+        *elem = repeated;
+        return true;
+    }
+    
     elem.data_mut().splice(idx..idx, vec![c; n]);
     
     true
@@ -69,7 +82,7 @@ mod tests {
         
         for _ in 0..10 {
             let mut stream = stream.clone();
-            mutate_repeat_token::<_, 16>(&mut rand, &mut stream, 32);
+            mutate_repeat_token::<_, _, 16>(&mut rand, &mut stream, 32);
             let size = stream.serialize_content(&mut buffer);
             let s = std::str::from_utf8(&buffer[0..size]).unwrap();
             println!("{s}");
@@ -84,7 +97,7 @@ mod tests {
         
         for _ in 0..10 {
             let mut stream = stream.clone();
-            mutate_repeat_char::<_, 16>(&mut rand, &mut stream);
+            mutate_repeat_char::<_, _, 16>(&mut rand, &mut stream);
             let size = stream.serialize_content(&mut buffer);
             let s = std::str::from_utf8(&buffer[0..size]).unwrap();
             println!("{s}");
@@ -6,7 +6,7 @@ const SPECIAL: [u8; 33] = [
     b'{', b'|', b'}', 127,
 ];
 
-pub fn mutate_special_insert<R: Rand>(rand: &mut R, stream: &mut TokenStream) -> bool {
+pub fn mutate_special_insert<R: Rand, T>(rand: &mut R, stream: &mut TokenStream<T>) -> bool {
     if stream.is_empty() {
         return false;
     }
@@ -25,7 +25,7 @@ pub fn mutate_special_insert<R: Rand>(rand: &mut R, stream: &mut TokenStream) ->
     false
 }
 
-pub fn mutate_special_replace<R: Rand>(rand: &mut R, stream: &mut TokenStream) -> bool {
+pub fn mutate_special_replace<R: Rand, T>(rand: &mut R, stream: &mut TokenStream<T>) -> bool {
     if stream.is_empty() {
         return false;
     }
@@ -1,7 +1,7 @@
 use crate::tokens::{TokenStream, TextToken};
 use libafl_bolts::prelude::{Rand, HasLen};
 
-pub fn mutate_split<R: Rand>(rand: &mut R, stream: &mut TokenStream, max_len: usize) -> bool {
+pub fn mutate_split<R: Rand, T>(rand: &mut R, stream: &mut TokenStream<T>, max_len: usize) -> bool {
     if stream.is_empty() || max_len.saturating_sub(stream.len()) < 2 {
         return false;
     }
@@ -22,6 +22,11 @@ pub fn mutate_split<R: Rand>(rand: &mut R, stream: &mut TokenStream, max_len: us
     let mut split_elem = token.clone_nodata();
     *split_elem.data_mut() = token.data_mut().split_off(pos);
     
+    if token.is_structured() && !(token.verify() && split_elem.verify()) {
+        token.data_mut().extend_from_slice(split_elem.data());
+        return false;
+    }
+    
     let new_elem = match rand.between(0, 3) {
         0 => TextToken::random_number::<_, 16>(rand),
         1 => TextToken::random_whitespace::<_, 1, 16>(rand),
@@ -4,7 +4,7 @@ use libafl::prelude::Tokens;
 use smallvec::SmallVec;
 use std::ops::Range;
 
-pub fn mutate_swap_tokens<R: Rand>(rand: &mut R, stream: &mut TokenStream) -> bool {
+pub fn mutate_swap_tokens<R: Rand, T>(rand: &mut R, stream: &mut TokenStream<T>) -> bool {
     if stream.is_empty() {
         return false;
     }
@@ -21,7 +21,7 @@ pub fn mutate_swap_tokens<R: Rand>(rand: &mut R, stream: &mut TokenStream) -> bo
     true
 }
 
-pub fn mutate_swap_constants<R: Rand>(rand: &mut R, stream: &mut TokenStream, dict: &Tokens) -> bool {
+pub fn mutate_swap_constants<R: Rand, T>(rand: &mut R, stream: &mut TokenStream<T>, dict: &Tokens) -> bool {
     if stream.is_empty() || dict.is_empty() {
         return false;
     }
@@ -46,7 +46,7 @@ fn range_dist(a: &Range<usize>, b: &Range<usize>) -> usize {
     a_len.wrapping_sub(b_len)
 }
 
-pub fn mutate_swap_words<R: Rand>(rand: &mut R, stream: &mut TokenStream) -> bool {
+pub fn mutate_swap_words<R: Rand, T>(rand: &mut R, stream: &mut TokenStream<T>) -> bool {
     if stream.len() < 3 {
         return false;
     }
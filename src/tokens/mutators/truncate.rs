@@ -1,7 +1,7 @@
 use crate::tokens::TokenStream;
 use libafl_bolts::prelude::{Rand, HasLen};
 
-pub fn mutate_truncate<R: Rand>(rand: &mut R, stream: &mut TokenStream) -> bool {
+pub fn mutate_truncate<R: Rand, T>(rand: &mut R, stream: &mut TokenStream<T>) -> bool {
     if stream.is_empty() {
         return false;
     }
@@ -18,7 +18,19 @@ pub fn mutate_truncate<R: Rand>(rand: &mut R, stream: &mut TokenStream) -> bool
     if new_len == 1 && elem.is_number() && matches!(elem.data().first(), Some(b'-') | Some(b'+')) {
         return false;
     }
-     
+    
+    if elem.is_structured() {
+        let mut truncated = elem.clone();
+        truncated.data_mut().truncate(new_len);
+        
+        if !truncated.verify() {
+            return false;
+        }
+        
+        *elem = truncated;
+        return true;
+    }
+    
     elem.data_mut().truncate(new_len);
     
     true
@@ -0,0 +1,155 @@
+use crate::tokens::TextToken;
+
+/// Splits raw bytes into [`TextToken`]s.
+///
+/// Implement this to lex protocol-specific syntax, like IMAP literals or SIP URIs,
+/// into tokens that the existing mutators understand. The data of the returned
+/// tokens must add up to the input, every token should pass [`TextToken::verify`].
+/// [`DefaultTokenizer`] can lex the parts that need no special treatment.
+///
+/// A fuzzer picks its tokenizer with the type parameter of [`TokenStream`](crate::tokens::TokenStream),
+/// which lexes with it everywhere a stream is parsed, seeds loaded by LibAFL included.
+/// Tokenizers that are only known at runtime go to [`TokenStream::tokenize_with`](crate::tokens::TokenStream::tokenize_with)
+/// or [`Retokenize::from_file_with`](crate::tokens::Retokenize::from_file_with).
+pub trait Tokenizer {
+    fn tokenize(&self, data: &[u8]) -> Vec<TextToken>;
+}
+
+/// The built-in lexer: whitespace, hex numbers, floats, octal numbers, numbers,
+/// quoted strings, delimiters and text, in this order. Everything else becomes raw bytes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultTokenizer;
+
+impl Tokenizer for DefaultTokenizer {
+    fn tokenize(&self, data: &[u8]) -> Vec<TextToken> {
+        let mut stream = Vec::new();
+        let mut cursor = 0;
+        
+        while cursor < data.len() {
+            if let Some(token) = TextToken::try_parse_whitespace(&data[cursor..]) {
+                cursor += token.len();
+                stream.push(token);
+            } else if let Some(token) = TextToken::try_parse_hex(&data[cursor..]) {
+                cursor += token.len();
+                stream.push(token);
+            } else if let Some(token) = TextToken::try_parse_float(&data[cursor..]).filter(|_| cursor == 0 || data[cursor - 1] != b'.') {
+                cursor += token.len();
+                stream.push(token);
+            } else if let Some(token) = TextToken::try_parse_octal(&data[cursor..]) {
+                cursor += token.len();
+                stream.push(token);
+            } else if let Some(token) = TextToken::try_parse_number(&data[cursor..]) {
+                cursor += token.len();
+                stream.push(token);
+            } else if let Some(token) = TextToken::try_parse_quoted(&data[cursor..]) {
+                cursor += token.len();
+                stream.push(token);
+            } else if let Some(token) = TextToken::try_parse_delimiter(&data[cursor..]) {
+                cursor += token.len();
+                stream.push(token);
+            } else if let Some(token) = TextToken::try_parse_text(&data[cursor..]) {
+                cursor += token.len();
+                stream.push(token);
+            } else {
+                let token = TextToken::parse_raw(&data[cursor..]);
+                cursor += token.len();
+                stream.push(token);
+            }
+        }
+        
+        stream
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::{Packet, PacketBasedInput};
+    use crate::tokens::{TokenStream, Retokenize};
+    use libafl::prelude::{
+        Input, Executor, ExitKind, HasObservers, Error, StdState, StdFuzzer, InMemoryCorpus, QueueScheduler,
+        ConstFeedback, NopEventManager, HasCorpus, Corpus,
+    };
+    use libafl_bolts::prelude::{RefIndexable, StdRand};
+    
+    /* Keeps the content of IMAP literals {N}\r\n in a single constant */
+    #[derive(Default)]
+    struct LiteralTokenizer;
+    
+    impl Tokenizer for LiteralTokenizer {
+        fn tokenize(&self, data: &[u8]) -> Vec<TextToken> {
+            let literal = data.windows(3).position(|w| w == b"}\r\n").and_then(|end| {
+                let start = data[..end].iter().rposition(|c| *c == b'{')?;
+                let len = std::str::from_utf8(&data[start + 1..end]).ok()?.parse::<usize>().ok()?;
+                Some((end + 3, std::cmp::min(end + 3 + len, data.len())))
+            });
+            let Some((start, end)) = literal else {
+                return DefaultTokenizer.tokenize(data);
+            };
+            let mut tokens = DefaultTokenizer.tokenize(&data[..start]);
+            tokens.push(TextToken::Constant(data[start..end].to_vec()));
+            tokens.extend(self.tokenize(&data[end..]));
+            tokens
+        }
+    }
+    
+    #[test]
+    fn test_tokenizer() {
+        let data = b"A1 LOGIN {11}\r\nuser 12 pwd secret\r\n";
+        let stream = TokenStream::tokenize_with(&LiteralTokenizer, data);
+        assert!(stream.tokens().iter().any(|t| t.is_constant() && t.data() == b"user 12 pwd"));
+        assert_eq!(stream.content_to_vec(), data);
+        
+        assert!(!TokenStream::from_bytes(data).tokens().iter().any(|t| t.is_constant()));
+        
+        let path = std::env::temp_dir().join(format!("butterfly-tokenizer-{}.txt", std::process::id()));
+        std::fs::write(&path, data).unwrap();
+        let loaded = PacketBasedInput::<TokenStream>::from_file_with(&LiteralTokenizer, &path).unwrap();
+        let default = PacketBasedInput::<TokenStream>::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        
+        assert_eq!(loaded.packets()[0].tokens().len(), stream.tokens().len());
+        assert!(!default.packets()[0].tokens().iter().any(|t| t.is_constant()));
+    }
+    
+    struct NopExecutor(());
+    
+    impl HasObservers for NopExecutor {
+        type Observers = ();
+        
+        fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+            RefIndexable::from(&self.0)
+        }
+        
+        fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+            RefIndexable::from(&mut self.0)
+        }
+    }
+    
+    impl<EM, I, S, Z> Executor<EM, I, S, Z> for NopExecutor {
+        fn run_target(&mut self, _fuzzer: &mut Z, _state: &mut S, _mgr: &mut EM, _input: &I) -> Result<ExitKind, Error> {
+            Ok(ExitKind::Ok)
+        }
+    }
+    
+    #[test]
+    fn test_load_seeds() {
+        let dir = std::env::temp_dir().join(format!("butterfly-seeds-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("login.txt"), b"A1 LOGIN {11}\r\nuser 12 pwd secret\r\n").unwrap();
+        
+        let mut feedback = ConstFeedback::False;
+        let mut objective = ConstFeedback::False;
+        let mut state = StdState::new(StdRand::with_seed(0), InMemoryCorpus::<PacketBasedInput<TokenStream<LiteralTokenizer>>>::new(), InMemoryCorpus::new(), &mut feedback, &mut objective).unwrap();
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), feedback, objective);
+        let result = state.load_initial_inputs_forced(&mut fuzzer, &mut NopExecutor(()), &mut NopEventManager::new(), std::slice::from_ref(&dir));
+        std::fs::remove_dir_all(&dir).unwrap();
+        result.unwrap();
+        
+        /* LibAFL lexes the seeds with the tokenizer of the input type */
+        let id = state.corpus().first().unwrap();
+        let testcase = state.corpus().get(id).unwrap().borrow();
+        let packet = &testcase.input().as_ref().unwrap().packets()[0];
+        assert!(packet.tokens().iter().any(|t| t.is_constant() && t.data() == b"user 12 pwd"));
+    }
+}
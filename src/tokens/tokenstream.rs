@@ -1,17 +1,44 @@
 use serde::{Serialize, Deserialize};
 use std::str::FromStr;
-use libafl_bolts::prelude::{Rand, nonzero, HasLen};
-use libafl::prelude::{Input, HasRand, Error};
-use crate::packets::{Packet, RandomPacketCreator, SplitPacket};
-use std::io::Read;
+use libafl_bolts::prelude::{Rand, nonzero, HasLen, generic_hash_std};
+use libafl::prelude::{Input, HasRand, Error, CorpusId};
+use crate::packets::{Packet, PacketMeta, RandomPacketCreator, SplitPacket, SplitBoundary, MergePacket, ShrinkPacket, ShiftPacket};
+use crate::tokens::{shrink_tokens, Tokenizer, DefaultTokenizer};
+use crate::format::{FormattedInput, InputFormat, read_file, write_file};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::marker::PhantomData;
 use std::path::Path;
 
+/// Characters that form a [`TextToken::Delimiter`].
+pub(crate) const DELIMITERS: [u8; 18] = [b'=', b',', b';', b':', b'.', b'/', b'&', b'?', b'|', b'(', b')', b'[', b']', b'{', b'}', b'<', b'>', b'@'];
+
+/* Length of the sign and the leading 0, 0x or 0o of hex and octal numbers */
+#[inline]
+pub(crate) fn number_prefix(data: &[u8]) -> usize {
+    let sign = usize::from(matches!(data.first(), Some(b'+') | Some(b'-')));
+    sign + 1 + usize::from(matches!(data.get(sign + 1), Some(b'x') | Some(b'X') | Some(b'o') | Some(b'O')))
+}
+
 #[derive(Clone, Serialize, Deserialize, Hash)]
 pub enum TextToken {
     Constant(Vec<u8>),
     Number(Vec<u8>),
     Whitespace(Vec<u8>),
     Text(Vec<u8>),
+    /// `0x1F`, optionally with a sign
+    Hex(Vec<u8>),
+    /// `0755` or `0o755`, optionally with a sign
+    Octal(Vec<u8>),
+    /// `3.14`, `1e-5` or `-2.5E+3`
+    Float(Vec<u8>),
+    /// A string in single or double quotes, including the quotes.
+    /// A backslash escapes the next character.
+    Quoted(Vec<u8>),
+    /// A single character out of `=,;:./&?|()[]{}<>@`
+    Delimiter(Vec<u8>),
+    /// Bytes that are not ASCII, like UTF-8 sequences, Latin-1 text or binary data
+    Raw(Vec<u8>),
 }
 
 impl std::fmt::Debug for TextToken {
@@ -33,12 +60,33 @@ impl std::fmt::Debug for TextToken {
                 let s = std::str::from_utf8(arg0).map_err(|_| std::fmt::Error {})?;
                 write!(f, "Text({s:?})")
             },
+            Self::Hex(arg0) => {
+                let s = std::str::from_utf8(arg0).map_err(|_| std::fmt::Error {})?;
+                write!(f, "Hex({s:?})")
+            },
+            Self::Octal(arg0) => {
+                let s = std::str::from_utf8(arg0).map_err(|_| std::fmt::Error {})?;
+                write!(f, "Octal({s:?})")
+            },
+            Self::Float(arg0) => {
+                let s = std::str::from_utf8(arg0).map_err(|_| std::fmt::Error {})?;
+                write!(f, "Float({s:?})")
+            },
+            Self::Quoted(arg0) => {
+                let s = std::str::from_utf8(arg0).map_err(|_| std::fmt::Error {})?;
+                write!(f, "Quoted({s:?})")
+            },
+            Self::Delimiter(arg0) => {
+                let s = std::str::from_utf8(arg0).map_err(|_| std::fmt::Error {})?;
+                write!(f, "Delimiter({s:?})")
+            },
+            Self::Raw(arg0) => write!(f, "Raw(\"{}\")", arg0.escape_ascii()),
         }
     }
 }
 
 impl TextToken {
-    fn try_parse_whitespace(data: &[u8]) -> Option<Self> {
+    pub(crate) fn try_parse_whitespace(data: &[u8]) -> Option<Self> {
         let mut len = 0;
         
         for byte in data {
@@ -56,7 +104,7 @@ impl TextToken {
         }
     }
     
-    fn try_parse_number(data: &[u8]) -> Option<Self> {
+    pub(crate) fn try_parse_number(data: &[u8]) -> Option<Self> {
         let mut sign = 0;
         let mut len = 0;
         
@@ -79,18 +127,138 @@ impl TextToken {
         }
     }
     
-    fn try_parse_text(data: &[u8]) -> Option<Self> {
-        const BLACKLIST: [u8; 18] = [
+    pub(crate) fn try_parse_hex(data: &[u8]) -> Option<Self> {
+        let sign = usize::from(matches!(data.first(), Some(b'+') | Some(b'-')));
+        
+        if data.get(sign) != Some(&b'0') || !matches!(data.get(sign + 1), Some(b'x') | Some(b'X')) {
+            return None;
+        }
+        
+        let len = data[sign + 2..].iter().take_while(|c| c.is_ascii_hexdigit()).count();
+        
+        if len == 0 {
+            None
+        } else {
+            Some(TextToken::Hex(data[0..sign + 2 + len].to_vec()))
+        }
+    }
+    
+    pub(crate) fn try_parse_octal(data: &[u8]) -> Option<Self> {
+        let sign = usize::from(matches!(data.first(), Some(b'+') | Some(b'-')));
+        
+        if data.get(sign) != Some(&b'0') {
+            return None;
+        }
+        
+        let prefix = sign + 1 + usize::from(matches!(data.get(sign + 1), Some(b'o') | Some(b'O')));
+        let len = data[prefix..].iter().take_while(|c| matches!(c, b'0'..=b'7')).count();
+        
+        /* Something like 089 is a decimal number */
+        if len == 0 || data.get(prefix + len).is_some_and(|c| c.is_ascii_digit()) {
+            None
+        } else {
+            Some(TextToken::Octal(data[0..prefix + len].to_vec()))
+        }
+    }
+    
+    pub(crate) fn try_parse_float(data: &[u8]) -> Option<Self> {
+        let digits = |data: &[u8], start: usize| data[start..].iter().take_while(|c| c.is_ascii_digit()).count();
+        let mut len = usize::from(matches!(data.first(), Some(b'+') | Some(b'-')));
+        let integer = digits(data, len);
+        
+        if integer == 0 {
+            return None;
+        }
+        
+        len += integer;
+        let mut is_float = false;
+        
+        if data.get(len) == Some(&b'.') {
+            let fraction = digits(data, len + 1);
+            
+            if fraction > 0 {
+                len += 1 + fraction;
+                is_float = true;
+            }
+        }
+        
+        if matches!(data.get(len), Some(b'e') | Some(b'E')) {
+            let sign = usize::from(matches!(data.get(len + 1), Some(b'+') | Some(b'-')));
+            let exponent = digits(data, len + 1 + sign);
+            
+            if exponent > 0 {
+                len += 1 + sign + exponent;
+                is_float = true;
+            }
+        }
+        
+        /* Dotted sequences like IP addresses and versions are not floats.
+           The DefaultTokenizer also rejects floats that directly follow a dot. */
+        if !is_float || (data.get(len) == Some(&b'.') && data.get(len + 1).is_some_and(|c| c.is_ascii_digit())) {
+            None
+        } else {
+            Some(TextToken::Float(data[0..len].to_vec()))
+        }
+    }
+    
+    pub(crate) fn try_parse_quoted(data: &[u8]) -> Option<Self> {
+        let quote = *data.first()?;
+        
+        if quote != b'"' && quote != b'\'' {
+            return None;
+        }
+        
+        let mut i = 1;
+        
+        while i < data.len() {
+            match data[i] {
+                c if c >= 0x80 || c == b'\n' => return None,
+                b'\\' => {
+                    if data.get(i + 1).is_none_or(|c| *c >= 0x80 || *c == b'\n') {
+                        return None;
+                    }
+                    
+                    i += 2;
+                },
+                c if c == quote => return Some(TextToken::Quoted(data[0..=i].to_vec())),
+                _ => i += 1,
+            }
+        }
+        
+        None
+    }
+    
+    pub(crate) fn try_parse_delimiter(data: &[u8]) -> Option<Self> {
+        let c = *data.first()?;
+        
+        if DELIMITERS.contains(&c) {
+            Some(TextToken::Delimiter(vec![c]))
+        } else {
+            None
+        }
+    }
+    
+    /* Never fails so that every byte slice can be tokenized */
+    pub(crate) fn parse_raw(data: &[u8]) -> Self {
+        let len = data.iter().take_while(|c| **c >= 0x80).count();
+        TextToken::Raw(data[0..std::cmp::max(len, 1)].to_vec())
+    }
+    
+    pub(crate) fn try_parse_text(data: &[u8]) -> Option<Self> {
+        const BLACKLIST: [u8; 20] = [
             // Whitespace
             b' ', b'\t', b'\n', 0x0b, 0x0c, b'\r',
             
             // Number
             b'+', b'-', b'0', b'1', b'2', b'3', b'4', b'5', b'6', b'7', b'8', b'9',
+            
+            // Quoted
+            b'"', b'\'',
         ];
         let mut len = 0;
         
         for byte in data {
-            if *byte >= 0x80 || (BLACKLIST.contains(byte) && len > 0) {
+            if *byte >= 0x80 || ((BLACKLIST.contains(byte) || DELIMITERS.contains(byte)) && len > 0) {
                 break;
             } else {
                 len += 1;
@@ -170,13 +338,19 @@ impl TextToken {
             TextToken::Number(_) => TextToken::Number(Vec::new()),
             TextToken::Whitespace(_) => TextToken::Whitespace(Vec::new()),
             TextToken::Text(_) => TextToken::Text(Vec::new()),
+            TextToken::Hex(_) => TextToken::Hex(Vec::new()),
+            TextToken::Octal(_) => TextToken::Octal(Vec::new()),
+            TextToken::Float(_) => TextToken::Float(Vec::new()),
+            TextToken::Quoted(_) => TextToken::Quoted(Vec::new()),
+            TextToken::Delimiter(_) => TextToken::Delimiter(Vec::new()),
+            TextToken::Raw(_) => TextToken::Raw(Vec::new()),
         }
     }
     
-    #[cfg(test)]
+    /// Checks whether the data of the token is valid for its class.
     pub fn verify(&self) -> bool {
         match self {
-            TextToken::Constant(_) => true,
+            TextToken::Constant(_) | TextToken::Raw(_) => true,
             TextToken::Number(data) => {
                 for (i, byte) in data.iter().enumerate() {
                     match *byte {
@@ -215,8 +389,20 @@ impl TextToken {
                 }
                 true
             },
+            TextToken::Hex(data) => TextToken::try_parse_hex(data).is_some_and(|t| t.len() == data.len()),
+            TextToken::Octal(data) => TextToken::try_parse_octal(data).is_some_and(|t| t.len() == data.len()),
+            TextToken::Float(data) => TextToken::try_parse_float(data).is_some_and(|t| t.len() == data.len()),
+            TextToken::Quoted(data) => TextToken::try_parse_quoted(data).is_some_and(|t| t.len() == data.len()),
+            TextToken::Delimiter(data) => data.len() == 1 && DELIMITERS.contains(&data[0]),
         }
     }
+    
+    /// Whether the token belongs to one of the classes with a fixed syntax
+    /// (hex, octal, float, quoted or delimiter), whose data cannot be cut arbitrarily.
+    #[inline]
+    pub fn is_structured(&self) -> bool {
+        matches!(self, TextToken::Hex(_) | TextToken::Octal(_) | TextToken::Float(_) | TextToken::Quoted(_) | TextToken::Delimiter(_))
+    }
 }
 
 impl TextToken {
@@ -226,7 +412,13 @@ impl TextToken {
             TextToken::Constant(data) |
             TextToken::Number(data) |
             TextToken::Whitespace(data) |
-            TextToken::Text(data) => data,
+            TextToken::Text(data) |
+            TextToken::Hex(data) |
+            TextToken::Octal(data) |
+            TextToken::Float(data) |
+            TextToken::Quoted(data) |
+            TextToken::Delimiter(data) |
+            TextToken::Raw(data) => data,
         }
     }
     
@@ -236,7 +428,13 @@ impl TextToken {
             TextToken::Constant(data) |
             TextToken::Number(data) |
             TextToken::Whitespace(data) |
-            TextToken::Text(data) => data,
+            TextToken::Text(data) |
+            TextToken::Hex(data) |
+            TextToken::Octal(data) |
+            TextToken::Float(data) |
+            TextToken::Quoted(data) |
+            TextToken::Delimiter(data) |
+            TextToken::Raw(data) => data,
         }
     }
     
@@ -264,39 +462,82 @@ impl TextToken {
     }
 }
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize, Hash)]
-pub struct TokenStream(Vec<TextToken>);
+/// A sequence of [`TextToken`]s.
+///
+/// The [`Tokenizer`] `T` lexes the stream whenever it is parsed from raw bytes,
+/// for example when LibAFL loads the seeds of a fuzzer from txt files.
+/// It is not part of the serialized stream.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct TokenStream<T = DefaultTokenizer>(Vec<TextToken>, #[serde(skip)] PhantomData<fn() -> T>);
+
+impl<T> Default for TokenStream<T> {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl<T> Clone for TokenStream<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.0.clone())
+    }
+}
 
-impl FromStr for TokenStream {
-    type Err = u8;
+impl<T> std::fmt::Debug for TokenStream<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("TokenStream").field(&self.0).finish()
+    }
+}
 
+impl<T> Hash for TokenStream<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<T> FromStr for TokenStream<T>
+where
+    T: Tokenizer + Default,
+{
+    type Err = std::convert::Infallible;
+    
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let s = s.as_bytes();
-        let mut stream = Vec::new();
-        let mut cursor = 0;
-        
-        while cursor < s.len() {
-            if let Some(token) = TextToken::try_parse_whitespace(&s[cursor..]) {
-                cursor += token.len();
-                stream.push(token);
-            } else if let Some(token) = TextToken::try_parse_number(&s[cursor..]) {
-                cursor += token.len();
-                stream.push(token);
-            } else if let Some(token) = TextToken::try_parse_text(&s[cursor..]) {
-                cursor += token.len();
-                stream.push(token);
-            } else {
-                return Err(s[cursor]);
-            }
-        }
-        
-        Ok(TokenStream(stream))
+        Ok(Self::tokenize(s.as_bytes()))
     }
 }
 
 impl TokenStream {
+    /// Splits arbitrary bytes into tokens with the [`DefaultTokenizer`].
+    /// This never fails.
+    pub fn from_bytes(s: &[u8]) -> Self {
+        Self::tokenize(s)
+    }
+    
+    /// Splits arbitrary bytes into tokens with a specific tokenizer.
+    pub fn tokenize_with<U: Tokenizer + ?Sized>(tokenizer: &U, s: &[u8]) -> Self {
+        Self::lex_with(tokenizer, s)
+    }
+}
+
+impl<T> TokenStream<T>
+where
+    T: Tokenizer + Default,
+{
+    /// Splits arbitrary bytes into tokens with the tokenizer of the stream.
+    pub fn tokenize(s: &[u8]) -> Self {
+        Self::lex_with(&T::default(), s)
+    }
+}
+
+impl<T> TokenStream<T> {
+    fn lex_with<U: Tokenizer + ?Sized>(tokenizer: &U, s: &[u8]) -> Self {
+        let tokens = tokenizer.tokenize(s);
+        debug_assert_eq!(tokens.iter().map(|t| t.len()).sum::<usize>(), s.len());
+        Self::new(tokens)
+    }
+    
     pub fn new(tokens: Vec<TextToken>) -> Self {
-        Self(tokens)
+        Self(tokens, PhantomData)
     }
     
     #[inline]
@@ -310,27 +551,58 @@ impl TokenStream {
     }
 }
 
-impl Input for TokenStream {
+impl<T> FormattedInput for TokenStream<T>
+where
+    T: Tokenizer + Default,
+{
+    fn parse_format(format: InputFormat, bytes: &[u8]) -> Option<Self> {
+        match format {
+            InputFormat::Txt => Some(Self::tokenize(bytes)),
+            InputFormat::Postcard => postcard::from_bytes(bytes).ok(),
+            InputFormat::Frames | InputFormat::Pcap => None,
+        }
+    }
+    
+    fn serialize_format(&self, format: InputFormat) -> Option<Vec<u8>> {
+        match format {
+            InputFormat::Txt => Some(self.content_to_vec()),
+            InputFormat::Postcard => postcard::to_allocvec(self).ok(),
+            InputFormat::Frames | InputFormat::Pcap => None,
+        }
+    }
+    
+    fn supports_format(format: InputFormat) -> bool {
+        matches!(format, InputFormat::Txt | InputFormat::Postcard)
+    }
+}
+
+impl<T> Input for TokenStream<T>
+where
+    T: Tokenizer + Default,
+{
     fn from_file<F>(path: F) -> Result<Self, Error>
     where
         F: AsRef<Path>,
     {
-        let path = path.as_ref();
-        let mut file = std::fs::File::open(path)?;
-        let mut bytes = vec![];
-        file.read_to_end(&mut bytes)?;
-        
-        match path.extension().and_then(|s| s.to_str()) {
-            Some("txt") => {
-                let s = std::str::from_utf8(&bytes).expect("Input txt file is not valid UTF-8");
-                s.parse::<Self>().map_err(|_| Error::serialize(format!("Could not parse txt file {}", path.display())))
-            },
-            _ => Ok(postcard::from_bytes(&bytes)?),
-        }
+        read_file(path.as_ref())
+    }
+    
+    fn to_file<F>(&self, path: F) -> Result<(), Error>
+    where
+        F: AsRef<Path>,
+    {
+        write_file(self, path.as_ref())
+    }
+    
+    fn generate_name(&self, _id: Option<CorpusId>) -> String {
+        format!("{:016x}", generic_hash_std(self))
     }
 }
 
-impl Packet for TokenStream {
+impl<T> Packet for TokenStream<T>
+where
+    T: Tokenizer + Default,
+{
     fn serialize_content(&self, buffer: &mut [u8]) -> usize {
         let mut cursor = 0;
         
@@ -345,18 +617,30 @@ impl Packet for TokenStream {
     }
     
     fn deserialize_content(buffer: &[u8]) -> Option<Self> {
-        let s = std::str::from_utf8(buffer).ok()?;
-        s.parse().ok()
+        Some(Self::tokenize(buffer))
+    }
+    
+    fn serialized_len(&self) -> usize {
+        self.tokens().iter().map(|t| t.len()).sum()
+    }
+    
+    fn write_content<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        for token in self.tokens() {
+            writer.write_all(token.data())?;
+        }
+        Ok(())
     }
 }
 
-impl HasLen for TokenStream {
+impl<T> PacketMeta for TokenStream<T> where T: Tokenizer + Default {}
+
+impl<T> HasLen for TokenStream<T> {
     fn len(&self) -> usize {
         self.0.len()
     }
 }
 
-impl<S> RandomPacketCreator<S> for TokenStream
+impl<S, T> RandomPacketCreator<S> for TokenStream<T>
 where
     S: HasRand,
 {
@@ -379,7 +663,7 @@ where
     }
 }
 
-impl<S> SplitPacket<S> for TokenStream
+impl<S, T> SplitPacket<S> for TokenStream<T>
 where
     S: HasRand,
 {
@@ -389,6 +673,118 @@ where
         }
         let idx = 1 + state.rand_mut().between(0, self.0.len() - 2);
         let other_self = self.0.split_off(idx);
-        Some(TokenStream(other_self))
+        Some(Self::new(other_self))
+    }
+    
+    /// Line boundaries are after whitespace that contains a newline,
+    /// word boundaries are between whitespace and the following token.
+    fn split_packet_at(&mut self, state: &mut S, boundary: SplitBoundary) -> Option<Self> {
+        if self.0.len() < 2 {
+            return None;
+        }
+        
+        let mut candidates = Vec::new();
+        
+        if boundary == SplitBoundary::Line {
+            candidates = self.boundaries(|prev, _| matches!(prev, TextToken::Whitespace(data) if data.contains(&b'\n')));
+        }
+        
+        if boundary != SplitBoundary::Random && candidates.is_empty() {
+            candidates = self.boundaries(|prev, next| prev.is_whitespace() && !next.is_whitespace());
+        }
+        
+        let Some(idx) = state.rand_mut().choose(candidates) else {
+            return self.split_packet(state);
+        };
+        let other_self = self.0.split_off(idx);
+        Some(Self::new(other_self))
+    }
+}
+
+impl<T> TokenStream<T> {
+    /* Indices that would leave no packet empty and where the tokens around the split satisfy `f` */
+    fn boundaries<F>(&self, f: F) -> Vec<usize>
+    where
+        F: Fn(&TextToken, &TextToken) -> bool,
+    {
+        (1..self.0.len()).filter(|idx| f(&self.0[idx - 1], &self.0[*idx])).collect()
+    }
+}
+
+impl<T> MergePacket for TokenStream<T> {
+    fn merge_packet(&mut self, other: &Self) -> bool {
+        self.0.extend_from_slice(&other.0);
+        true
+    }
+}
+
+impl<T> ShiftPacket for TokenStream<T> {
+    fn shift_units(&self) -> usize {
+        self.0.len()
+    }
+    
+    fn shift_boundary(&mut self, next: &mut Self, at: usize) -> bool {
+        if at > self.0.len() + next.0.len() {
+            return false;
+        }
+        
+        let mut tokens = std::mem::take(&mut self.0);
+        tokens.append(&mut next.0);
+        next.0 = tokens.split_off(at);
+        self.0 = tokens;
+        true
+    }
+}
+
+impl<T> ShrinkPacket for TokenStream<T> {
+    fn shrink_packet<F>(&mut self, keep: &mut F) -> Result<(), Error>
+    where
+        F: FnMut(&Self) -> Result<bool, Error>,
+    {
+        shrink_tokens(self, keep)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_token_classes() {
+        let stream = "SET 0x1F 0755 -0o17 3.14 2.5E+3 10.0.0.1 089 \"a \\\" b\" 'c' key=value".parse::<TokenStream>().unwrap();
+        let classes: Vec<String> = stream.tokens()
+            .iter()
+            .filter(|t| !t.is_whitespace())
+            .map(|t| format!("{t:?}"))
+            .collect();
+        
+        assert_eq!(classes, [
+            "Text(\"SET\")",
+            "Hex(\"0x1F\")",
+            "Octal(\"0755\")",
+            "Octal(\"-0o17\")",
+            "Float(\"3.14\")",
+            "Float(\"2.5E+3\")",
+            "Number(\"10\")",
+            "Delimiter(\".\")",
+            "Number(\"0\")",
+            "Delimiter(\".\")",
+            "Number(\"0\")",
+            "Delimiter(\".\")",
+            "Number(\"1\")",
+            "Number(\"089\")",
+            "Quoted(\"\\\"a \\\\\\\" b\\\"\")",
+            "Quoted(\"'c'\")",
+            "Text(\"key\")",
+            "Delimiter(\"=\")",
+            "Text(\"value\")",
+        ]);
+        
+        for token in stream.tokens() {
+            assert!(token.verify());
+        }
+        
+        /* Unterminated quotes are text */
+        assert!(matches!("\"abc".parse::<TokenStream>().unwrap().tokens()[0], TextToken::Text(_)));
     }
 }